@@ -14,6 +14,42 @@ fn main() {
 
     f.write_all(link_x).unwrap();
 
+    let packages = ["t016", "q024", "t038", "q040"];
+    if selected(&packages).len() > 1 {
+        panic!("Only one package feature can be selected");
+    }
+
+    // Generate the memory layout only if a flash size is selected, otherwise
+    // the application provides its own memory.x
+    let flash_sizes = ["flash-8k", "flash-16k", "flash-32k", "flash-64k"];
+    let flash_size = match selected(&flash_sizes)[..] {
+        [] => None,
+        ["flash-8k"] => Some(8),
+        ["flash-16k"] => Some(16),
+        ["flash-32k"] => Some(32),
+        ["flash-64k"] => Some(64),
+        _ => panic!("Only one flash size feature can be selected"),
+    };
+    if let Some(flash_size) = flash_size {
+        let mut memory = File::create(out.join("memory.x")).unwrap();
+        writeln!(
+            memory,
+            r#"MEMORY
+{{
+  /* NOTE K = KiBi = 1024 bytes */
+  FLASH : ORIGIN = 0x10001000, LENGTH = {}K
+  RAM : ORIGIN = 0x20000000, LENGTH = 16K
+}}
+
+/* This is where the call stack will be allocated. */
+/* The stack is of the full descending type. */
+/* NOTE Do NOT modify `_stack_start` unless you know what you are doing */
+_stack_start = ORIGIN(RAM) + LENGTH(RAM);"#,
+            flash_size
+        )
+        .unwrap();
+    }
+
     // *IMPORTANT*: The weak aliases (i.e. `PROVIDED`) must come *after* `EXTERN(__INTERRUPTS)`.
     // Otherwise the linker will ignore user defined interrupts and always populate the table
     // with the weak aliases.
@@ -49,3 +85,15 @@ handlers.");
     println!("cargo:rerun-if-changed=exceptions.s");
     println!("cargo:rerun-if-changed=xmc.x.in");
 }
+
+/// Returns the enabled features out of `features`
+fn selected<'a>(features: &[&'a str]) -> Vec<&'a str> {
+    features
+        .iter()
+        .filter(|feature| {
+            let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+            env::var_os(var).is_some()
+        })
+        .cloned()
+        .collect()
+}
@@ -30,7 +30,7 @@ fn main() -> ! {
             let tx = port2.p2_1.into_alternate_af6(&cs);
             let rx = usic::dx3pin_to_dx0pin(rx, &mut usic);
             // Create usart
-            let mut serial = Serial::usic0_ch0(usic, (tx, rx), Bps(9600), &mut scu);
+            let mut serial = Serial::usic0_ch0(usic, (tx, rx), Bps(9600), &mut scu).unwrap();
             loop {
                 // Wait for reception of a single byte
                 let received = nb::block!(serial.read()).unwrap();
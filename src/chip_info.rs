@@ -0,0 +1,95 @@
+//! API for the chip identification
+//!
+//! The identification is read from the SCU and the factory data in the flash
+//! configuration sector.
+
+use crate::scu::Scu;
+
+/// Chip variant identification in the flash configuration sector
+const CHIPID: *const [u32; 8] = 0x1000_0F00 as *const _;
+/// Unique chip identification in the flash configuration sector
+const UCID: *const [u8; 16] = 0x1000_0FF0 as *const _;
+/// Offset of the flash size in the chip variant identification
+const CHIPID_FLASH_END: usize = 6;
+/// Size of the flash configuration sectors before the user flash
+const FLASH_CONFIG_SIZE: u32 = 0x1000;
+/// All XMC1100 variants have 16K of ram
+const RAM_SIZE: u32 = 16 * 1024;
+
+/// Package of the chip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Package {
+    /// TSSOP-16
+    T016,
+    /// VQFN-24
+    Q024,
+    /// TSSOP-38
+    T038,
+    /// VQFN-40
+    Q040,
+}
+
+/// Chip identification
+#[derive(Clone, Copy)]
+pub struct ChipInfo {
+    idchip: u32,
+    chip_id: [u32; 8],
+    ucid: [u8; 16],
+    revision: u8,
+}
+
+impl ChipInfo {
+    /// Reads the chip identification
+    pub fn read(scu: &Scu) -> Self {
+        let scu_general = &scu.scu_general;
+        // NOTE(unsafe) read-only access to the flash configuration sector
+        let (chip_id, ucid) = unsafe { (*CHIPID, *UCID) };
+        ChipInfo {
+            idchip: scu_general.idchip.read().bits(),
+            chip_id,
+            ucid,
+            revision: scu_general.dbgromid.read().version().bits(),
+        }
+    }
+
+    /// Returns the raw chip id (`IDCHIP`)
+    pub fn idchip(&self) -> u32 {
+        self.idchip
+    }
+
+    /// Returns the raw chip variant identification
+    pub fn chip_id(&self) -> [u32; 8] {
+        self.chip_id
+    }
+
+    /// Returns the package of the chip, if it's known
+    pub fn package(&self) -> Option<Package> {
+        match (self.idchip >> 4) & 0xF {
+            1 => Some(Package::T016),
+            2 => Some(Package::Q024),
+            3 => Some(Package::T038),
+            4 => Some(Package::Q040),
+            _ => None,
+        }
+    }
+
+    /// Returns the size of the user flash in bytes
+    pub fn flash_size(&self) -> u32 {
+        self.chip_id[CHIPID_FLASH_END].saturating_sub(FLASH_CONFIG_SIZE)
+    }
+
+    /// Returns the size of the ram in bytes
+    pub fn ram_size(&self) -> u32 {
+        RAM_SIZE
+    }
+
+    /// Returns the product version of the rom
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    /// Returns the 128-bit unique chip id
+    pub fn unique_id(&self) -> [u8; 16] {
+        self.ucid
+    }
+}
@@ -40,6 +40,12 @@ impl Delay {
 
         Delay { scale }
     }
+
+    /// Uses updated clocks, e.g. after trimming
+    pub fn update_clocks(&mut self, scu: &Scu) {
+        assert!(scu.clocks.sysclk().0 >= 1_000_000);
+        self.scale = scu.clocks.sysclk().0 / 1_000_000;
+    }
 }
 
 impl DelayMs<u32> for Delay {
@@ -0,0 +1,376 @@
+//! API for the event request unit (ERU0)
+//!
+//! The ERU0 has four channels, each made up of an event request select unit
+//! (ERS), an event trigger logic (ETL) and an output gating unit (OGU). The ERS
+//! selects the input signals, the ETL detects edges on them and sends trigger
+//! pulses to any OGU. The OGU generates the interrupt and the trigger signals
+//! `ERU0.PDOUTy`, `ERU0.GOUTy` and `ERU0.IOUTy`. `ERU0.GOUTy` can be selected
+//! as external event of a CCU4 slice with `timers::Timer::select_eru_event`,
+//! so the timer starts or captures exactly on an external edge, or as external
+//! trigger of the VADC background request source with `Ogu::trigger_vadc`, so
+//! a conversion starts on the edge.
+//!
+//! `split` takes ownership of ERU0, so the channels can't be used together with
+//! `gpio::ExtiPin`, which configures a whole channel on its own.
+
+use core::marker::PhantomData;
+
+use cortex_m::interrupt;
+
+use crate::gpio::{Edge, ErsInput, ExtiPin};
+use crate::xmc1100::{Interrupt, ERU0, VADC};
+
+// Trigger inputs of the VADC background request source (`BRSCTRL.XTSEL`)
+// connected to `ERU0.GOUT0` to `ERU0.GOUT3`, according to the VADC interconnect
+// table in the XMC1100 reference manual, 12 is VADC.BGREQTRM
+const VADC_ERU_GOUT_TRIGGERS: [u8; 4] = [12, 13, 14, 15];
+
+/// Extension trait to split the ERU0 into its channels
+pub trait EruExt {
+    /// Splits the ERU0 into its channels
+    fn split(self) -> Parts;
+}
+
+/// ERU0 channel
+pub trait Channel {
+    /// Index of the channel
+    const INDEX: usize;
+}
+
+/// Channel 0 (type state)
+pub struct CH0;
+/// Channel 1 (type state)
+pub struct CH1;
+/// Channel 2 (type state)
+pub struct CH2;
+/// Channel 3 (type state)
+pub struct CH3;
+
+impl Channel for CH0 {
+    const INDEX: usize = 0;
+}
+impl Channel for CH1 {
+    const INDEX: usize = 1;
+}
+impl Channel for CH2 {
+    const INDEX: usize = 2;
+}
+impl Channel for CH3 {
+    const INDEX: usize = 3;
+}
+
+/// ERU0 parts
+pub struct Parts {
+    pub ers0: Ers<CH0>,
+    pub ers1: Ers<CH1>,
+    pub ers2: Ers<CH2>,
+    pub ers3: Ers<CH3>,
+    pub etl0: Etl<CH0>,
+    pub etl1: Etl<CH1>,
+    pub etl2: Etl<CH2>,
+    pub etl3: Etl<CH3>,
+    pub ogu0: Ogu<CH0>,
+    pub ogu1: Ogu<CH1>,
+    pub ogu2: Ogu<CH2>,
+    pub ogu3: Ogu<CH3>,
+}
+
+impl EruExt for ERU0 {
+    fn split(self) -> Parts {
+        Parts {
+            ers0: Ers { _ch: PhantomData },
+            ers1: Ers { _ch: PhantomData },
+            ers2: Ers { _ch: PhantomData },
+            ers3: Ers { _ch: PhantomData },
+            etl0: Etl { _ch: PhantomData },
+            etl1: Etl { _ch: PhantomData },
+            etl2: Etl { _ch: PhantomData },
+            etl3: Etl { _ch: PhantomData },
+            ogu0: Ogu { _ch: PhantomData },
+            ogu1: Ogu { _ch: PhantomData },
+            ogu2: Ogu { _ch: PhantomData },
+            ogu3: Ogu { _ch: PhantomData },
+        }
+    }
+}
+
+/// Event request select unit
+pub struct Ers<CH> {
+    _ch: PhantomData<CH>,
+}
+
+impl<CH: Channel> Ers<CH> {
+    /// Selects the source (0 to 3) of an input
+    pub fn select_source(&mut self, input: ErsInput, source: u8) {
+        debug_assert!(source < 4);
+        let shift = CH::INDEX * 4
+            + match input {
+                ErsInput::A => 0,
+                ErsInput::B => 2,
+            };
+        interrupt::free(|_| {
+            // NOTE(unsafe) only the source of this input is changed
+            unsafe {
+                (*ERU0::ptr()).exisel.modify(|r, w| {
+                    w.bits((r.bits() & !(0b11 << shift)) | (u32::from(source) << shift))
+                })
+            }
+        });
+    }
+
+    /// Selects a pin connected to this channel as source
+    pub fn select_pin<P: ExtiPin<Channel = CH>>(&mut self, _pin: &P) {
+        let eru_input = P::eru_input();
+        self.select_source(eru_input.input, eru_input.source);
+    }
+
+    /// Inverts an input
+    pub fn set_inverted(&mut self, input: ErsInput, inverted: bool) {
+        interrupt::free(|_| unsafe {
+            (*ERU0::ptr()).exicon[CH::INDEX].modify(|_, w| match input {
+                ErsInput::A => w.na().bit(inverted),
+                ErsInput::B => w.nb().bit(inverted),
+            })
+        });
+    }
+}
+
+/// Combination of the inputs which is passed to the event trigger logic
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputCombination {
+    /// Input A
+    A,
+    /// Input B
+    B,
+    /// Input A or input B
+    AOrB,
+    /// Input A and input B
+    AAndB,
+}
+
+/// Event trigger logic
+pub struct Etl<CH> {
+    _ch: PhantomData<CH>,
+}
+
+impl<CH: Channel> Etl<CH> {
+    /// Selects the input combination and the edge which is detected on it
+    pub fn configure(&mut self, combination: InputCombination, edge: Edge) {
+        let (rising, falling) = match edge {
+            Edge::Rising => (true, false),
+            Edge::Falling => (false, true),
+            Edge::Both => (true, true),
+        };
+        interrupt::free(|_| unsafe {
+            (*ERU0::ptr()).exicon[CH::INDEX].modify(|_, w| {
+                let w = match combination {
+                    InputCombination::A => w.ss().value1(),
+                    InputCombination::B => w.ss().value2(),
+                    InputCombination::AOrB => w.ss().value3(),
+                    InputCombination::AAndB => w.ss().value4(),
+                };
+                w.re().bit(rising).fe().bit(falling)
+            })
+        });
+    }
+
+    /// Sends a trigger pulse to the output gating unit on each detected edge
+    pub fn trigger<OCH: Channel>(&mut self, _ogu: &Ogu<OCH>) {
+        interrupt::free(|_| unsafe {
+            // NOTE(unsafe) the index is a valid output channel
+            (*ERU0::ptr()).exicon[CH::INDEX]
+                .modify(|_, w| w.ocs().bits(OCH::INDEX as u8).pe().set_bit())
+        });
+    }
+
+    /// Stops sending trigger pulses
+    pub fn disable_trigger(&mut self) {
+        interrupt::free(|_| unsafe {
+            (*ERU0::ptr()).exicon[CH::INDEX].modify(|_, w| w.pe().clear_bit())
+        });
+    }
+
+    /// Makes the status flag follow the level of the selected edge instead of
+    /// being sticky
+    pub fn set_level_detection(&mut self, level: bool) {
+        interrupt::free(|_| unsafe {
+            (*ERU0::ptr()).exicon[CH::INDEX].modify(|_, w| w.ld().bit(level))
+        });
+    }
+
+    /// Returns true if an edge was detected
+    pub fn is_pending(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*ERU0::ptr()).exicon[CH::INDEX].read().fl().bit_is_set() }
+    }
+
+    /// Clears the status flag
+    pub fn clear_pending(&mut self) {
+        interrupt::free(|_| unsafe {
+            (*ERU0::ptr()).exicon[CH::INDEX].modify(|_, w| w.fl().clear_bit())
+        });
+    }
+}
+
+/// Peripheral trigger input of an output gating unit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeripheralTrigger {
+    /// No peripheral trigger
+    Disabled,
+    /// `ERU_OGUy1`
+    Input1,
+    /// `ERU_OGUy2`
+    Input2,
+    /// `ERU_OGUy3`
+    Input3,
+}
+
+/// Gating of the trigger pulses, which controls `ERU0.GOUTy` and `ERU0.IOUTy`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gating {
+    /// No trigger pulses are passed on
+    Never,
+    /// All trigger pulses are passed on
+    Always,
+    /// Trigger pulses are passed on while the pattern matches
+    PatternMatch,
+    /// Trigger pulses are passed on while the pattern doesn't match
+    PatternMismatch,
+}
+
+/// Pattern over the status flags of several event trigger logic channels
+///
+/// The pattern matches while all included status flags are set. With level
+/// detection enabled on the channels (`Etl::set_level_detection`), the flags
+/// follow the input levels, and the inputs can be inverted in the ERS to match
+/// on low levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pattern {
+    mask: u8,
+}
+
+impl Pattern {
+    /// Creates an empty pattern
+    pub fn new() -> Self {
+        Pattern { mask: 0 }
+    }
+
+    /// Includes the status flag of a channel in the pattern
+    pub fn include<CH: Channel>(mut self, _etl: &Etl<CH>) -> Self {
+        self.mask |= 1 << CH::INDEX;
+        self
+    }
+}
+
+/// Pattern detection result which generates the notification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notify {
+    /// The pattern starts to match
+    Match,
+    /// The pattern stops to match
+    Mismatch,
+}
+
+/// Output gating unit
+pub struct Ogu<CH> {
+    _ch: PhantomData<CH>,
+}
+
+impl<CH: Channel> Ogu<CH> {
+    /// Selects a peripheral signal, which generates trigger pulses in addition
+    /// to the event trigger logic
+    pub fn select_peripheral_trigger(&mut self, trigger: PeripheralTrigger) {
+        unsafe {
+            (*ERU0::ptr()).exocon[CH::INDEX].modify(|_, w| match trigger {
+                PeripheralTrigger::Disabled => w.iss().value1(),
+                PeripheralTrigger::Input1 => w.iss().value2(),
+                PeripheralTrigger::Input2 => w.iss().value3(),
+                PeripheralTrigger::Input3 => w.iss().value4(),
+            })
+        }
+    }
+
+    /// Selects which trigger pulses are passed on to the interrupt and the
+    /// peripheral triggers
+    pub fn set_gating(&mut self, gating: Gating) {
+        unsafe {
+            (*ERU0::ptr()).exocon[CH::INDEX].modify(|_, w| match gating {
+                Gating::Never => w.gp().value1(),
+                Gating::Always => w.gp().value2(),
+                Gating::PatternMatch => w.gp().value3(),
+                Gating::PatternMismatch => w.gp().value4(),
+            })
+        }
+    }
+
+    /// Selects the status flags, which are part of the pattern detection
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        unsafe {
+            (*ERU0::ptr()).exocon[CH::INDEX].modify(|_, w| {
+                w.ipen0()
+                    .bit(pattern.mask & 0b0001 != 0)
+                    .ipen1()
+                    .bit(pattern.mask & 0b0010 != 0)
+                    .ipen2()
+                    .bit(pattern.mask & 0b0100 != 0)
+                    .ipen3()
+                    .bit(pattern.mask & 0b1000 != 0)
+            })
+        }
+    }
+
+    /// Generates the interrupt and peripheral triggers when the result of the
+    /// pattern detection changes to a match or a mismatch
+    pub fn listen_pattern(&mut self, pattern: Pattern, notify: Notify) {
+        self.set_pattern(pattern);
+        self.set_gating(match notify {
+            Notify::Match => Gating::PatternMatch,
+            Notify::Mismatch => Gating::PatternMismatch,
+        });
+        unsafe { (*ERU0::ptr()).exocon[CH::INDEX].modify(|_, w| w.geen().set_bit()) }
+    }
+
+    /// Stops generating notifications for pattern detection result changes
+    pub fn unlisten_pattern(&mut self) {
+        unsafe { (*ERU0::ptr()).exocon[CH::INDEX].modify(|_, w| w.geen().clear_bit()) }
+    }
+
+    /// Returns true if the pattern currently matches
+    pub fn is_pattern_matched(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*ERU0::ptr()).exocon[CH::INDEX].read().pdr().bit_is_set() }
+    }
+
+    /// Selects the gated trigger output `ERU0.GOUTy` as external trigger of
+    /// the VADC background request source, which starts a conversion on the
+    /// edge
+    ///
+    /// Only the trigger is configured, the VADC has to be enabled and the
+    /// channels have to be selected in `BRSSEL` by the application
+    pub fn trigger_vadc(&mut self, vadc: &mut VADC, edge: Edge) {
+        let mode = match edge {
+            Edge::Falling => 1,
+            Edge::Rising => 2,
+            Edge::Both => 3,
+        };
+        vadc.brsctrl.modify(|_, w| unsafe {
+            w.xtwc()
+                .set_bit()
+                .xtsel()
+                .bits(VADC_ERU_GOUT_TRIGGERS[CH::INDEX])
+                .xtmode()
+                .bits(mode)
+        });
+        vadc.brsmr.modify(|_, w| w.entr().set_bit());
+    }
+
+    /// Returns the interrupt of the output gating unit
+    pub fn interrupt(&self) -> Interrupt {
+        match CH::INDEX {
+            0 => Interrupt::ERU0_0,
+            1 => Interrupt::ERU0_1,
+            2 => Interrupt::ERU0_2,
+            _ => Interrupt::ERU0_3,
+        }
+    }
+}
@@ -1,9 +1,11 @@
 //! General Purpose Input / Output
 
+use crate::eru::{self, Channel as _};
+use crate::xmc1100::{Interrupt, ERU0};
 use core::marker::PhantomData;
+use cortex_m::interrupt::CriticalSection;
 use void::Void;
 
-// TODO Implement marker for af with PushPull or OpenDrain
 /// Extension trait to split a GPIO peripheral in independent pins and registers
 pub trait GpioExt {
     /// The parts to split the GPIO into
@@ -19,6 +21,11 @@ trait GpioRegExt {
     fn is_set_low(&self, pos: u8) -> bool;
     fn set_high(&self, pos: u8);
     fn set_low(&self, pos: u8);
+    fn set_control(&self, pos: u8, pc: u8);
+    fn input(&self) -> u16;
+    fn output(&self) -> u16;
+    fn set_reset(&self, set: u16, reset: u16);
+    fn set_power_save(&self, pos: u8, power_save: bool);
 }
 
 pub struct AF0;
@@ -30,8 +37,28 @@ pub struct AF5;
 pub struct AF6;
 pub struct AF7;
 
-pub struct Alternate<MODE> {
-    _mode: PhantomData<MODE>,
+/// Alternate function (type state)
+///
+/// The output is push pull by default, `Alternate<AF, OpenDrain>` is an open
+/// drain alternate function
+pub struct Alternate<AF, OTYPE = PushPull> {
+    _af: PhantomData<AF>,
+    _otype: PhantomData<OTYPE>,
+}
+
+/// Hardware control by the first peripheral (type state)
+pub struct HW0;
+
+/// Hardware control by the second peripheral (type state)
+pub struct HW1;
+
+/// Hardware controlled pin (type state)
+///
+/// The peripheral selected with `HWSEL` controls the direction and output
+/// of the pin, e.g. for the data lines of dual and quad SPI. The peripheral
+/// also has to enable its hardware port control, see `usic::HwPortControl`.
+pub struct HwControlled<HW> {
+    _hw: PhantomData<HW>,
 }
 
 /// Input mode (type state)
@@ -48,6 +75,43 @@ pub struct PullDown;
 /// Pulled up input (type state)
 pub struct PullUp;
 
+/// Inverted input (type state)
+pub struct Inverted<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Pad hysteresis of an input
+pub enum Hysteresis {
+    /// Standard hysteresis
+    Standard,
+    /// Large hysteresis, for noisy or slow signals
+    Large,
+}
+
+/// Behaviour of a pin during deep sleep
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepMode {
+    /// The pin keeps operating, outputs hold their state
+    Active,
+    /// The output driver is tri-stated and the input is disconnected to
+    /// reduce leakage, pull devices stay active
+    PowerSave,
+}
+
+impl SleepMode {
+    fn is_power_save(self) -> bool {
+        match self {
+            SleepMode::Active => false,
+            SleepMode::PowerSave => true,
+        }
+    }
+}
+
+/// Analog mode (type state)
+///
+/// The digital input of the pad is disabled
+pub struct Analog;
+
 /// Open drain input or output (type state)
 pub struct OpenDrain;
 
@@ -61,6 +125,114 @@ pub struct PushPull;
 
 use embedded_hal::digital::v2::{toggleable, InputPin, OutputPin, StatefulOutputPin};
 
+/// Edge which triggers an external interrupt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Rising and falling edge
+    Both,
+}
+
+/// Input of an event request select unit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErsInput {
+    /// Input A
+    A,
+    /// Input B
+    B,
+}
+
+/// Event request unit input a pin is connected to
+#[derive(Clone, Copy, Debug)]
+pub struct EruInput {
+    /// Input of the event request select unit
+    pub input: ErsInput,
+    /// Source selected for the input in `EXISEL`
+    pub source: u8,
+}
+
+/// External interrupts on pins, generated by ERU0
+///
+/// Every pin occupies the ERU0 channel it's connected to, so only one pin per
+/// channel can be used at a time
+pub trait ExtiPin {
+    /// ERU0 channel the pin is connected to
+    ///
+    /// The event trigger logic and output gating unit of the same channel are
+    /// used for the interrupt
+    type Channel: eru::Channel;
+
+    /// Returns the ERU0 input the pin is connected to
+    fn eru_input() -> EruInput;
+
+    /// Returns the interrupt which is triggered by the pin
+    fn interrupt(&self) -> Interrupt {
+        match Self::Channel::INDEX {
+            0 => Interrupt::ERU0_0,
+            1 => Interrupt::ERU0_1,
+            2 => Interrupt::ERU0_2,
+            _ => Interrupt::ERU0_3,
+        }
+    }
+
+    /// Starts listening for the edge on the pin
+    fn listen(&mut self, edge: Edge, eru: &mut ERU0) {
+        let EruInput { input, source } = Self::eru_input();
+        let channel = Self::Channel::INDEX;
+        let shift = channel * 4
+            + match input {
+                ErsInput::A => 0,
+                ErsInput::B => 2,
+            };
+        // NOTE(unsafe) only the source of this input is changed
+        eru.exisel.modify(|r, w| unsafe {
+            w.bits((r.bits() & !(0b11 << shift)) | (u32::from(source) << shift))
+        });
+        let (rising, falling) = match edge {
+            Edge::Rising => (true, false),
+            Edge::Falling => (false, true),
+            Edge::Both => (true, true),
+        };
+        eru.exicon[channel].write(|w| {
+            let w = match input {
+                ErsInput::A => w.ss().value1(),
+                ErsInput::B => w.ss().value2(),
+            };
+            // NOTE(unsafe) the channel is a valid output channel
+            unsafe { w.ocs().bits(channel as u8) }
+                .re()
+                .bit(rising)
+                .fe()
+                .bit(falling)
+                .pe()
+                .set_bit()
+        });
+        eru.exocon[channel].write(|w| w.gp().value2());
+    }
+
+    /// Stops listening on the pin
+    fn unlisten(&mut self, eru: &mut ERU0) {
+        let channel = Self::Channel::INDEX;
+        eru.exicon[channel].modify(|_, w| w.pe().clear_bit());
+        eru.exocon[channel].write(|w| w.gp().value1());
+    }
+
+    /// Clears the pending edge of the pin
+    fn clear_interrupt_pending(&mut self, eru: &mut ERU0) {
+        let channel = Self::Channel::INDEX;
+        eru.exicon[channel].modify(|_, w| w.fl().clear_bit());
+    }
+
+    /// Returns true if the edge was detected on the pin
+    fn check_interrupt(&self, eru: &ERU0) -> bool {
+        let channel = Self::Channel::INDEX;
+        eru.exicon[channel].read().fl().bit_is_set()
+    }
+}
+
 /// Fully erased pin
 pub struct Pin<MODE> {
     i: u8,
@@ -74,6 +246,13 @@ unsafe impl<MODE> Sync for Pin<MODE> {}
 // threads
 unsafe impl<MODE> Send for Pin<MODE> {}
 
+impl<MODE> Pin<MODE> {
+    /// Selects the behaviour of the pin during deep sleep
+    pub fn set_sleep_mode(&mut self, mode: SleepMode, _cs: &CriticalSection) {
+        unsafe { (*self.port).set_power_save(self.i, mode.is_power_save()) }
+    }
+}
+
 impl<MODE> StatefulOutputPin for Pin<Output<MODE>> {
     fn is_set_high(&self) -> Result<bool, Void> {
         self.is_set_low().map(|x| !x)
@@ -119,8 +298,127 @@ impl<MODE> InputPin for Pin<Input<MODE>> {
     }
 }
 
+/// Error while building a [`PortBus`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PortBusError {
+    /// No pins were given
+    Empty,
+    /// The pins don't all belong to the same port
+    DifferentPorts,
+}
+
+/// Several pins of the same port, which are read and written at once
+///
+/// Bit `n` of the bus value corresponds to the `n`th pin passed to
+/// [`PortBus::new`]
+pub struct PortBus<MODE> {
+    port: *const dyn GpioRegExt,
+    pins: [u8; 16],
+    len: u8,
+    _mode: PhantomData<MODE>,
+}
+
+// NOTE(unsafe) The only write acess is to OMR, which is thread safe
+unsafe impl<MODE> Sync for PortBus<MODE> {}
+// NOTE(unsafe) this only enables read access to the same pins from multiple
+// threads
+unsafe impl<MODE> Send for PortBus<MODE> {}
+
+impl<MODE> PortBus<MODE> {
+    /// Builds a bus from erased pins, which all have to be part of the same
+    /// port
+    pub fn new<I>(pins: I) -> Result<Self, PortBusError>
+    where
+        I: IntoIterator<Item = Pin<MODE>>,
+    {
+        let mut bus: Option<Self> = None;
+        for pin in pins {
+            match bus {
+                None => {
+                    let mut positions = [0; 16];
+                    positions[0] = pin.i;
+                    bus = Some(PortBus {
+                        port: pin.port,
+                        pins: positions,
+                        len: 1,
+                        _mode: PhantomData,
+                    });
+                }
+                Some(ref mut bus) => {
+                    if bus.port as *const u8 != pin.port as *const u8 {
+                        return Err(PortBusError::DifferentPorts);
+                    }
+                    // Pins are unique, so there are at most 16 of the same port
+                    bus.pins[bus.len as usize] = pin.i;
+                    bus.len += 1;
+                }
+            }
+        }
+        bus.ok_or(PortBusError::Empty)
+    }
+
+    /// Releases the pins in the order they were passed to `new`
+    pub fn release(self) -> impl Iterator<Item = Pin<MODE>> {
+        let port = self.port;
+        let pins = self.pins;
+        (0..self.len as usize).map(move |n| Pin {
+            i: pins[n],
+            port,
+            _mode: PhantomData,
+        })
+    }
+
+    fn pins(&self) -> &[u8] {
+        &self.pins[..self.len as usize]
+    }
+
+    fn gather(&self, port_value: u16) -> u16 {
+        self.pins()
+            .iter()
+            .enumerate()
+            .fold(0, |value, (n, &i)| value | (((port_value >> i) & 1) << n))
+    }
+}
+
+impl<MODE> PortBus<Output<MODE>> {
+    /// Sets the state of all pins with a single write
+    pub fn write(&mut self, value: u16) {
+        let (set, reset) = self
+            .pins()
+            .iter()
+            .enumerate()
+            .fold((0, 0), |(set, reset), (n, &i)| {
+                if value & (1 << n) != 0 {
+                    (set | (1 << i), reset)
+                } else {
+                    (set, reset | (1 << i))
+                }
+            });
+        unsafe { (*self.port).set_reset(set, reset) }
+    }
+
+    /// Returns the state the pins are driven to
+    pub fn read_output(&self) -> u16 {
+        self.gather(unsafe { (*self.port).output() })
+    }
+}
+
+impl PortBus<Output<OpenDrain>> {
+    /// Reads the level of all pins at once
+    pub fn read(&self) -> u16 {
+        self.gather(unsafe { (*self.port).input() })
+    }
+}
+
+impl<MODE> PortBus<Input<MODE>> {
+    /// Reads the level of all pins at once
+    pub fn read(&self) -> u16 {
+        self.gather(unsafe { (*self.port).input() })
+    }
+}
+
 macro_rules! gpio_trait {
-    ($portx:ident) => {
+    ($portx:ident, [$($base:expr => $iocrx:ident),+] $(, $pdisc:ident)?) => {
         impl GpioRegExt for crate::xmc1100::$portx::RegisterBlock {
             fn is_low(&self, pos: u8) -> bool {
                 self.in_.read().bits() & (1 << pos) == 0
@@ -139,21 +437,195 @@ macro_rules! gpio_trait {
                 // NOTE(unsafe) atomic write to a stateless register
                 unsafe { self.omr.write(|w| w.bits(1 << (pos + 16))) }
             }
+
+            fn input(&self) -> u16 {
+                self.in_.read().bits() as u16
+            }
+
+            fn output(&self) -> u16 {
+                self.out.read().bits() as u16
+            }
+
+            fn set_reset(&self, set: u16, reset: u16) {
+                // NOTE(unsafe) atomic write to a stateless register
+                unsafe {
+                    self.omr
+                        .write(|w| w.bits(u32::from(set) | (u32::from(reset) << 16)))
+                }
+            }
+
+            fn set_power_save(&self, pos: u8, power_save: bool) {
+                // NOTE(unsafe) only the power save bit of this pin is changed
+                self.pps.modify(|r, w| unsafe {
+                    if power_save {
+                        w.bits(r.bits() | (1 << pos))
+                    } else {
+                        w.bits(r.bits() & !(1 << pos))
+                    }
+                });
+            }
+
+            fn set_control(&self, pos: u8, pc: u8) {
+                let shift = (pos % 4) * 8 + 3;
+                let mask = 0x1f << shift;
+                let value = u32::from(pc) << shift;
+                // NOTE(unsafe) only the control bits of this pin are changed
+                unsafe {
+                    $(
+                        // The digital input of the pad has to be enabled
+                        self.$pdisc.modify(|r, w| w.bits(r.bits() & !(1 << pos)));
+                    )?
+                    match pos & !0b11 {
+                        $(
+                            $base => self.$iocrx.modify(|r, w| {
+                                w.bits((r.bits() & !mask) | value)
+                            }),
+                        )+
+                        _ => unreachable!(),
+                    }
+                }
+            }
         }
     };
 }
 
-gpio_trait!(port0);
-gpio_trait!(port1);
-gpio_trait!(port2);
+gpio_trait!(port0, [0 => iocr0, 4 => iocr4, 8 => iocr8, 12 => iocr12]);
+gpio_trait!(port1, [0 => iocr0, 4 => iocr4]);
+gpio_trait!(port2, [0 => iocr0, 4 => iocr4, 8 => iocr8], pdisc);
+
+/// Operating mode of a [`DynamicPin`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dynamic {
+    /// Floating input
+    InputFloating,
+    /// Pulled up input
+    InputPullUp,
+    /// Pulled down input
+    InputPullDown,
+    /// Push pull output
+    OutputPushPull,
+    /// Open drain output
+    OutputOpenDrain,
+}
+
+impl Dynamic {
+    fn is_input(self) -> bool {
+        !matches!(self, Dynamic::OutputPushPull)
+    }
+
+    fn is_output(self) -> bool {
+        matches!(self, Dynamic::OutputPushPull | Dynamic::OutputOpenDrain)
+    }
+}
+
+/// Error of an operation which doesn't fit the current mode of a
+/// [`DynamicPin`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PinModeError {
+    /// The pin is in the wrong mode
+    IncorrectMode,
+}
+
+/// Fully erased pin, which can change its mode at runtime
+pub struct DynamicPin {
+    i: u8,
+    port: *const dyn GpioRegExt,
+    mode: Dynamic,
+}
+
+// NOTE(unsafe) The only write acess is to OMR, which is thread safe, changing
+// the mode requires a critical section
+unsafe impl Sync for DynamicPin {}
+// NOTE(unsafe) this only enables read access to the same pin from multiple
+// threads
+unsafe impl Send for DynamicPin {}
+
+impl DynamicPin {
+    /// Returns the current mode of the pin
+    pub fn mode(&self) -> Dynamic {
+        self.mode
+    }
+
+    fn set_mode(&mut self, mode: Dynamic, pc: u8) {
+        unsafe { (*self.port).set_control(self.i, pc) };
+        self.mode = mode;
+    }
+
+    /// Selects the behaviour of the pin during deep sleep
+    pub fn set_sleep_mode(&mut self, mode: SleepMode, _cs: &CriticalSection) {
+        unsafe { (*self.port).set_power_save(self.i, mode.is_power_save()) }
+    }
+
+    /// Configures the pin to operate as a floating input pin
+    pub fn make_floating_input(&mut self, _cs: &CriticalSection) {
+        self.set_mode(Dynamic::InputFloating, 0b00000);
+    }
+
+    /// Configures the pin to operate as a pulled up input pin
+    pub fn make_pull_up_input(&mut self, _cs: &CriticalSection) {
+        self.set_mode(Dynamic::InputPullUp, 0b00010);
+    }
+
+    /// Configures the pin to operate as a pulled down input pin
+    pub fn make_pull_down_input(&mut self, _cs: &CriticalSection) {
+        self.set_mode(Dynamic::InputPullDown, 0b00001);
+    }
+
+    /// Configures the pin to operate as a push pull output pin
+    pub fn make_push_pull_output(&mut self, _cs: &CriticalSection) {
+        self.set_mode(Dynamic::OutputPushPull, 0b10000);
+    }
+
+    /// Configures the pin to operate as an open drain output pin
+    pub fn make_open_drain_output(&mut self, _cs: &CriticalSection) {
+        self.set_mode(Dynamic::OutputOpenDrain, 0b11000);
+    }
+}
+
+impl OutputPin for DynamicPin {
+    type Error = PinModeError;
+    fn set_high(&mut self) -> Result<(), PinModeError> {
+        if self.mode.is_output() {
+            unsafe { (*self.port).set_high(self.i) };
+            Ok(())
+        } else {
+            Err(PinModeError::IncorrectMode)
+        }
+    }
+
+    fn set_low(&mut self) -> Result<(), PinModeError> {
+        if self.mode.is_output() {
+            unsafe { (*self.port).set_low(self.i) };
+            Ok(())
+        } else {
+            Err(PinModeError::IncorrectMode)
+        }
+    }
+}
+
+impl InputPin for DynamicPin {
+    type Error = PinModeError;
+    fn is_high(&self) -> Result<bool, PinModeError> {
+        self.is_low().map(|x| !x)
+    }
+
+    fn is_low(&self) -> Result<bool, PinModeError> {
+        if self.mode.is_input() {
+            Ok(unsafe { (*self.port).is_low(self.i) })
+        } else {
+            Err(PinModeError::IncorrectMode)
+        }
+    }
+}
 
-// TODO Find a nicer way to handle port2 being analog by default
 #[allow(unused)]
 macro_rules! gpio {
-    ($PORTX:ident, $portx:ident, $analog_hack:block, [
-        $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty, $iocrx:ident, $pcx:ident),)+
+    ($PORTX:ident, $portx:ident, [
+        $($(#[$attr:meta])* $PXi:ident: ($pxi:ident, $i:expr, $MODE:ty, $iocrx:ident, $pcx:ident, $phcrx:ident, $phx:ident $(, $pdisx:ident)?),)+
     ]) => {
         /// GPIO
+        // Imports are unused if the package has no pins of this port
+        #[allow(unused_imports)]
         pub mod $portx {
             use core::marker::PhantomData;
             use void::Void;
@@ -164,16 +636,17 @@ macro_rules! gpio {
             use cortex_m::interrupt::CriticalSection;
 
             use super::{
-                Floating, GpioExt, Input, OpenDrain, Output,
+                Analog, Floating, GpioExt, Hysteresis, Input, Inverted, OpenDrain, Output,
                 PullDown, PullUp, PushPull,
                 Alternate, AF0, AF1, AF2, AF3, AF4, AF5, AF6, AF7,
-                GpioRegExt, Pin,
+                Dynamic, DynamicPin, GpioRegExt, HwControlled, Pin, SleepMode, HW0, HW1,
             };
 
             /// GPIO parts
             pub struct Parts {
                 $(
                     /// Pin
+                    $(#[$attr])*
                     pub $pxi: $PXi<$MODE>,
                 )+
             }
@@ -182,9 +655,9 @@ macro_rules! gpio {
                 type Parts = Parts;
 
                 fn split(self) -> Parts {
-                    $analog_hack
                     Parts {
                         $(
+                            $(#[$attr])*
                             $pxi: $PXi { _mode: PhantomData },
                         )+
                     }
@@ -193,16 +666,19 @@ macro_rules! gpio {
 
             $(
                 /// Pin
+                $(#[$attr])*
                 pub struct $PXi<MODE> {
                     _mode: PhantomData<MODE>,
                 }
 
+                $(#[$attr])*
                 impl<MODE> $PXi<MODE> {
                     /// Configures the pin to operate as a floating input pin
                     pub fn into_floating_input(
                         self,
                         _cs: &CriticalSection
                     ) -> $PXi<Input<Floating>> {
+                        self.set_digital();
                         unsafe {
                             (*$PORTX::ptr()).$iocrx.modify(|_, w| {
                                 w.$pcx().value1()
@@ -216,6 +692,7 @@ macro_rules! gpio {
                         self,
                         _cs: &CriticalSection
                         ) -> $PXi<Input<PullDown>> {
+                        self.set_digital();
                         unsafe {
                             (*$PORTX::ptr()).$iocrx.modify(|_, w| {
                                 w.$pcx().value2()
@@ -229,6 +706,7 @@ macro_rules! gpio {
                         self,
                         _cs: &CriticalSection
                     ) -> $PXi<Input<PullUp>> {
+                        self.set_digital();
                         unsafe {
                             (*$PORTX::ptr()).$iocrx.modify(|_, w| {
                                 w.$pcx().value3()
@@ -237,11 +715,82 @@ macro_rules! gpio {
                         $PXi { _mode: PhantomData }
                     }
 
+                    /// Configures the pin to operate as an inverted floating input pin
+                    pub fn into_inverted_floating_input(
+                        self,
+                        _cs: &CriticalSection
+                    ) -> $PXi<Input<Inverted<Floating>>> {
+                        self.set_digital();
+                        unsafe {
+                            (*$PORTX::ptr()).$iocrx.modify(|_, w| {
+                                w.$pcx().value5()
+                            });
+                        }
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Configures the pin to operate as an inverted pulled down input pin
+                    pub fn into_inverted_pull_down_input(
+                        self,
+                        _cs: &CriticalSection
+                    ) -> $PXi<Input<Inverted<PullDown>>> {
+                        self.set_digital();
+                        unsafe {
+                            (*$PORTX::ptr()).$iocrx.modify(|_, w| {
+                                w.$pcx().value6()
+                            });
+                        }
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Configures the pin to operate as an inverted pulled up input pin
+                    pub fn into_inverted_pull_up_input(
+                        self,
+                        _cs: &CriticalSection
+                    ) -> $PXi<Input<Inverted<PullUp>>> {
+                        self.set_digital();
+                        unsafe {
+                            (*$PORTX::ptr()).$iocrx.modify(|_, w| {
+                                w.$pcx().value7()
+                            });
+                        }
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Selects the pad hysteresis of the pin
+                    pub fn set_hysteresis(
+                        &mut self,
+                        hysteresis: Hysteresis,
+                        _cs: &CriticalSection
+                    ) {
+                        let large = match hysteresis {
+                            Hysteresis::Standard => false,
+                            Hysteresis::Large => true,
+                        };
+                        unsafe {
+                            (*$PORTX::ptr()).$phcrx.modify(|_, w| {
+                                w.$phx().bit(large)
+                            });
+                        }
+                    }
+
+                    /// Selects the behaviour of the pin during deep sleep
+                    pub fn set_sleep_mode(
+                        &mut self,
+                        mode: SleepMode,
+                        _cs: &CriticalSection
+                    ) {
+                        unsafe {
+                            (*$PORTX::ptr()).set_power_save($i, mode.is_power_save())
+                        }
+                    }
+
                     /// Configures the pin to operate as an open drain output pin
                     pub fn into_open_drain_output(
                         self,
                         _cs: &CriticalSection
                     ) -> $PXi<Output<OpenDrain>> {
+                        self.set_digital();
                         unsafe {
                             (*$PORTX::ptr()).$iocrx.modify(|_, w| {
                                 w.$pcx().value17()
@@ -255,6 +804,7 @@ macro_rules! gpio {
                         self,
                         _cs: &CriticalSection
                     ) -> $PXi<Output<PushPull>> {
+                        self.set_digital();
                         unsafe {
                             (*$PORTX::ptr()).$iocrx.modify(|_, w| {
                                 w.$pcx().value9()
@@ -263,12 +813,94 @@ macro_rules! gpio {
                         $PXi { _mode: PhantomData }
                     }
 
-                    // TODO This always configures it as PushPull
-                    fn set_alternate_mode(&mut self, mode: u8) {
+                    /// Configures the pin as a floating input, which can change
+                    /// its mode at runtime
+                    pub fn into_dynamic(self, _cs: &CriticalSection) -> DynamicPin {
+                        self.set_digital();
+                        unsafe {
+                            (*$PORTX::ptr()).$iocrx.modify(|_, w| {
+                                w.$pcx().value1()
+                            });
+                        }
+                        DynamicPin {
+                            i: $i,
+                            port: $PORTX::ptr() as *const dyn GpioRegExt,
+                            mode: Dynamic::InputFloating,
+                        }
+                    }
+
+                    /// Configures the pin to be controlled by the first
+                    /// peripheral in `HWSEL`
+                    pub fn into_hw_controlled_hw0(
+                        self,
+                        _cs: &CriticalSection
+                    ) -> $PXi<HwControlled<HW0>> {
+                        self.set_digital();
+                        self.set_hw_control(0b01);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Configures the pin to be controlled by the second
+                    /// peripheral in `HWSEL`
+                    pub fn into_hw_controlled_hw1(
+                        self,
+                        _cs: &CriticalSection
+                    ) -> $PXi<HwControlled<HW1>> {
+                        self.set_digital();
+                        self.set_hw_control(0b10);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    fn set_hw_control(&self, hw: u32) {
+                        // NOTE(unsafe) only the bits of this pin are changed
+                        unsafe {
+                            (*$PORTX::ptr()).hwsel.modify(|r, w| {
+                                w.bits((r.bits() & !(0b11 << ($i * 2))) | (hw << ($i * 2)))
+                            });
+                        }
+                    }
+
+                    /// Enables the digital input of the pad and returns the
+                    /// control of the pin to software
+                    fn set_digital(&self) {
+                        self.set_hw_control(0b00);
+                        $(
+                            unsafe {
+                                (*$PORTX::ptr()).pdisc.modify(|_, w| {
+                                    w.$pdisx().clear_bit()
+                                });
+                            }
+                        )?
+                    }
+
+                    $(
+                        /// Configures the pin to operate as an analog pin
+                        ///
+                        /// This disables the digital input of the pad
+                        pub fn into_analog(
+                            self,
+                            _cs: &CriticalSection
+                        ) -> $PXi<Analog> {
+                            self.set_hw_control(0b00);
+                            unsafe {
+                                (*$PORTX::ptr()).$iocrx.modify(|_, w| {
+                                    w.$pcx().value1()
+                                });
+                                (*$PORTX::ptr()).pdisc.modify(|_, w| {
+                                    w.$pdisx().set_bit()
+                                });
+                            }
+                            $PXi { _mode: PhantomData }
+                        }
+                    )?
+
+                    fn set_alternate_mode(&mut self, mode: u8, open_drain: bool) {
                         debug_assert!(mode < 0b1000);
+                        self.set_digital();
+                        let otype = if open_drain { 0b01000 } else { 0 };
                         unsafe {
                             (*$PORTX::ptr()).$iocrx.modify(|_, w| {
-                                w.$pcx().bits(0b10000 | mode)
+                                w.$pcx().bits(0b10000 | otype | mode)
                             });
                         }
                     }
@@ -276,60 +908,117 @@ macro_rules! gpio {
                     pub fn into_alternate_af0(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF0>> {
-                        self.set_alternate_mode(0);
+                        self.set_alternate_mode(0, false);
                         $PXi { _mode: PhantomData }
                     }
                     pub fn into_alternate_af1(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF1>> {
-                        self.set_alternate_mode(1);
+                        self.set_alternate_mode(1, false);
                         $PXi { _mode: PhantomData }
                     }
 
                     pub fn into_alternate_af2(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF2>> {
-                        self.set_alternate_mode(2);
+                        self.set_alternate_mode(2, false);
                         $PXi { _mode: PhantomData }
                     }
 
                     pub fn into_alternate_af3(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF3>> {
-                        self.set_alternate_mode(3);
+                        self.set_alternate_mode(3, false);
                         $PXi { _mode: PhantomData }
                     }
 
                     pub fn into_alternate_af4(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF4>> {
-                        self.set_alternate_mode(4);
+                        self.set_alternate_mode(4, false);
                         $PXi { _mode: PhantomData }
                     }
 
                     pub fn into_alternate_af5(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF5>> {
-                        self.set_alternate_mode(5);
+                        self.set_alternate_mode(5, false);
                         $PXi { _mode: PhantomData }
                     }
 
                     pub fn into_alternate_af6(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF6>> {
-                        self.set_alternate_mode(6);
+                        self.set_alternate_mode(6, false);
                         $PXi { _mode: PhantomData }
                     }
 
                     pub fn into_alternate_af7(
                         mut self, _cs: &CriticalSection
                     ) -> $PXi<Alternate<AF7>> {
-                        self.set_alternate_mode(7);
+                        self.set_alternate_mode(7, false);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af0(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF0, OpenDrain>> {
+                        self.set_alternate_mode(0, true);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af1(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF1, OpenDrain>> {
+                        self.set_alternate_mode(1, true);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af2(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF2, OpenDrain>> {
+                        self.set_alternate_mode(2, true);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af3(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF3, OpenDrain>> {
+                        self.set_alternate_mode(3, true);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af4(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF4, OpenDrain>> {
+                        self.set_alternate_mode(4, true);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af5(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF5, OpenDrain>> {
+                        self.set_alternate_mode(5, true);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af6(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF6, OpenDrain>> {
+                        self.set_alternate_mode(6, true);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    pub fn into_alternate_open_drain_af7(
+                        mut self, _cs: &CriticalSection
+                    ) -> $PXi<Alternate<AF7, OpenDrain>> {
+                        self.set_alternate_mode(7, true);
                         $PXi { _mode: PhantomData }
                     }
 
                 }
 
+                $(#[$attr])*
                 impl<MODE> $PXi<MODE> {
                     /// Erases the pin number from the type
                     ///
@@ -344,6 +1033,7 @@ macro_rules! gpio {
                     }
                 }
 
+                $(#[$attr])*
                 impl<MODE> StatefulOutputPin for $PXi<Output<MODE>> {
                     fn is_set_high(&self) -> Result<bool, Void> {
                         self.is_set_low().map(|x| !x)
@@ -354,6 +1044,7 @@ macro_rules! gpio {
                     }
                 }
 
+                $(#[$attr])*
                 impl<MODE> OutputPin for $PXi<Output<MODE>> {
                     type Error = Void;
                     fn set_high(&mut self) -> Result<(), Void> {
@@ -365,8 +1056,10 @@ macro_rules! gpio {
                     }
                 }
 
+                $(#[$attr])*
                 impl<MODE> toggleable::Default for $PXi<Output<MODE>> {}
 
+                $(#[$attr])*
                 impl InputPin for $PXi<Output<OpenDrain>> {
                     type Error = Void;
                     fn is_high(&self) -> Result<bool, Void> {
@@ -378,6 +1071,7 @@ macro_rules! gpio {
                     }
                 }
 
+                $(#[$attr])*
                 impl<MODE> InputPin for $PXi<Input<MODE>> {
                     type Error = Void;
                     fn is_high(&self) -> Result<bool, Void> {
@@ -393,47 +1087,71 @@ macro_rules! gpio {
     }
 }
 
-gpio!(PORT0, port0, {}, [
-    P0_0: (p0_0, 0, Input<Floating>, iocr0, pc0),
-    P0_1: (p0_1, 1, Input<Floating>, iocr0, pc1),
-    P0_2: (p0_2, 2, Input<Floating>, iocr0, pc2),
-    P0_3: (p0_3, 3, Input<Floating>, iocr0, pc3),
-    P0_4: (p0_4, 4, Input<Floating>, iocr4, pc4),
-    P0_5: (p0_5, 5, Input<Floating>, iocr4, pc5),
-    P0_6: (p0_6, 6, Input<Floating>, iocr4, pc6),
-    P0_7: (p0_7, 7, Input<Floating>, iocr4, pc7),
-    P0_8: (p0_8, 8, Input<Floating>, iocr8, pc8),
-    P0_9: (p0_9, 9, Input<Floating>, iocr8, pc9),
-    P0_10: (p0_10, 10, Input<Floating>, iocr8, pc10),
-    P0_11: (p0_11, 11, Input<Floating>, iocr8, pc11),
-    P0_12: (p0_12, 12, Input<Floating>, iocr12, pc12),
-    P0_13: (p0_13, 13, Input<Floating>, iocr12, pc13),
-    P0_14: (p0_14, 14, Input<Floating>, iocr12, pc14),
-    P0_15: (p0_15, 15, Input<Floating>, iocr12, pc15),
+// Pins which don't exist in the package selected with the cargo features are
+// left out, so they can't be used by accident
+gpio!(PORT0, port0, [
+    P0_0: (p0_0, 0, Input<Floating>, iocr0, pc0, phcr0, ph0),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_1: (p0_1, 1, Input<Floating>, iocr0, pc1, phcr0, ph1),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_2: (p0_2, 2, Input<Floating>, iocr0, pc2, phcr0, ph2),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_3: (p0_3, 3, Input<Floating>, iocr0, pc3, phcr0, ph3),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_4: (p0_4, 4, Input<Floating>, iocr4, pc4, phcr0, ph4),
+    P0_5: (p0_5, 5, Input<Floating>, iocr4, pc5, phcr0, ph5),
+    P0_6: (p0_6, 6, Input<Floating>, iocr4, pc6, phcr0, ph6),
+    P0_7: (p0_7, 7, Input<Floating>, iocr4, pc7, phcr0, ph7),
+    P0_8: (p0_8, 8, Input<Floating>, iocr8, pc8, phcr1, ph8),
+    P0_9: (p0_9, 9, Input<Floating>, iocr8, pc9, phcr1, ph9),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_10: (p0_10, 10, Input<Floating>, iocr8, pc10, phcr1, ph10),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_11: (p0_11, 11, Input<Floating>, iocr8, pc11, phcr1, ph11),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_12: (p0_12, 12, Input<Floating>, iocr12, pc12, phcr1, ph12),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_13: (p0_13, 13, Input<Floating>, iocr12, pc13, phcr1, ph13),
+    P0_14: (p0_14, 14, Input<Floating>, iocr12, pc14, phcr1, ph14),
+    P0_15: (p0_15, 15, Input<Floating>, iocr12, pc15, phcr1, ph15),
 ]);
 
-gpio!(PORT1, port1, {}, [
-    P1_0: (p1_0, 0, Input<Floating>, iocr0, pc0),
-    P1_1: (p1_1, 1, Input<Floating>, iocr0, pc1),
-    P1_2: (p1_2, 2, Input<Floating>, iocr0, pc2),
-    P1_3: (p1_3, 3, Input<Floating>, iocr0, pc3),
-    P1_4: (p1_4, 4, Input<Floating>, iocr4, pc4),
-    P1_5: (p1_5, 5, Input<Floating>, iocr4, pc5),
-    P1_6: (p1_6, 6, Input<Floating>, iocr4, pc6),
+gpio!(PORT1, port1, [
+    #[cfg(not(feature = "t016"))]
+    P1_0: (p1_0, 0, Input<Floating>, iocr0, pc0, phcr0, ph0),
+    #[cfg(not(feature = "t016"))]
+    P1_1: (p1_1, 1, Input<Floating>, iocr0, pc1, phcr0, ph1),
+    #[cfg(not(feature = "t016"))]
+    P1_2: (p1_2, 2, Input<Floating>, iocr0, pc2, phcr0, ph2),
+    #[cfg(not(feature = "t016"))]
+    P1_3: (p1_3, 3, Input<Floating>, iocr0, pc3, phcr0, ph3),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P1_4: (p1_4, 4, Input<Floating>, iocr4, pc4, phcr0, ph4),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P1_5: (p1_5, 5, Input<Floating>, iocr4, pc5, phcr0, ph5),
+    #[cfg(not(any(feature = "t016", feature = "q024", feature = "t038")))]
+    P1_6: (p1_6, 6, Input<Floating>, iocr4, pc6, phcr0, ph6),
 ]);
 
-gpio!(PORT2, port2, {unsafe {(*PORT2::ptr()).pdisc.write(|w| w.bits(0))}
-}, [
-    P2_0: (p2_0, 0, Input<Floating>, iocr0, pc0),
-    P2_1: (p2_1, 1, Input<Floating>, iocr0, pc1),
-    P2_2: (p2_2, 2, Input<Floating>, iocr0, pc2),
-    P2_3: (p2_3, 3, Input<Floating>, iocr0, pc3),
-    P2_4: (p2_4, 4, Input<Floating>, iocr4, pc4),
-    P2_5: (p2_5, 5, Input<Floating>, iocr4, pc5),
-    P2_6: (p2_6, 6, Input<Floating>, iocr4, pc6),
-    P2_7: (p2_7, 7, Input<Floating>, iocr4, pc7),
-    P2_8: (p2_8, 8, Input<Floating>, iocr8, pc8),
-    P2_9: (p2_9, 9, Input<Floating>, iocr8, pc9),
-    P2_10: (p2_10, 10, Input<Floating>, iocr8, pc10),
-    P2_11: (p2_11, 11, Input<Floating>, iocr8, pc11),
+// Port 2 pins are analog by default, the digital pad is enabled when they're
+// converted into a digital mode
+gpio!(PORT2, port2, [
+    P2_0: (p2_0, 0, Analog, iocr0, pc0, phcr0, ph0, pdis0),
+    #[cfg(not(feature = "t016"))]
+    P2_1: (p2_1, 1, Analog, iocr0, pc1, phcr0, ph1, pdis1),
+    #[cfg(not(feature = "t016"))]
+    P2_2: (p2_2, 2, Analog, iocr0, pc2, phcr0, ph2, pdis2),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P2_3: (p2_3, 3, Analog, iocr0, pc3, phcr0, ph3, pdis3),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P2_4: (p2_4, 4, Analog, iocr4, pc4, phcr0, ph4, pdis4),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P2_5: (p2_5, 5, Analog, iocr4, pc5, phcr0, ph5, pdis5),
+    P2_6: (p2_6, 6, Analog, iocr4, pc6, phcr0, ph6, pdis6),
+    P2_7: (p2_7, 7, Analog, iocr4, pc7, phcr0, ph7, pdis7),
+    #[cfg(not(feature = "t016"))]
+    P2_8: (p2_8, 8, Analog, iocr8, pc8, phcr1, ph8, pdis8),
+    P2_9: (p2_9, 9, Analog, iocr8, pc9, phcr1, ph9, pdis9),
+    P2_10: (p2_10, 10, Analog, iocr8, pc10, phcr1, ph10, pdis10),
+    P2_11: (p2_11, 11, Analog, iocr8, pc11, phcr1, ph11, pdis11),
 ]);
@@ -3,12 +3,17 @@
 
 pub use xmc1100;
 
+pub mod chip_info;
 pub mod delay;
+pub mod eru;
 pub mod gpio;
 pub mod pin_mappings;
+pub mod power;
 pub mod prelude;
 pub mod scu;
 pub mod serial;
+pub mod supply;
+pub mod temperature;
 pub mod time;
 pub mod timers;
 pub mod usic;
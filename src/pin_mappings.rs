@@ -1,4 +1,6 @@
+use crate::eru::{CH0, CH1, CH2, CH3};
 use crate::gpio::port0::*;
+#[cfg(not(feature = "t016"))]
 use crate::gpio::port1::*;
 use crate::gpio::port2::*;
 use crate::gpio::*;
@@ -7,13 +9,16 @@ use crate::usic::*;
 use xmc1100::*;
 
 macro_rules! pins {
-    ($($PIN:ident => {
+    ($($(#[$attr:meta])* $PIN:ident => {
         $($AF:ty: $TRAIT:ty),+
     }),+) => {
         $(
-            $(
-                impl $TRAIT for $PIN<Alternate<$AF>> {}
-            )+
+            $(#[$attr])*
+            const _: () = {
+                $(
+                    impl<OTYPE> $TRAIT for $PIN<Alternate<$AF, OTYPE>> {}
+                )+
+            };
         )+
     }
 }
@@ -23,30 +28,67 @@ pins! {
     P0_7 => {AF7: Dout0Pin<USIC0_CH1>},
     P0_14 => {AF6: Dout0Pin<USIC0_CH0>},
     P0_15 => {AF6: Dout0Pin<USIC0_CH0>},
+    #[cfg(not(feature = "t016"))]
     P1_0 => {AF7: Dout0Pin<USIC0_CH0>},
+    #[cfg(not(feature = "t016"))]
     P1_1 => {AF6: Dout0Pin<USIC0_CH0>},
+    #[cfg(not(feature = "t016"))]
     P1_2 => {AF7: Dout0Pin<USIC0_CH1>},
+    #[cfg(not(feature = "t016"))]
     P1_3 => {AF7: Dout0Pin<USIC0_CH1>},
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
     P1_5 => {AF2: Dout0Pin<USIC0_CH0>},
+    #[cfg(not(any(feature = "t016", feature = "q024", feature = "t038")))]
     P1_6 => {AF2: Dout0Pin<USIC0_CH1>},
     P2_0 => {AF6: Dout0Pin<USIC0_CH0>},
+    #[cfg(not(feature = "t016"))]
     P2_1 => {AF6: Dout0Pin<USIC0_CH0>},
     P2_10 => {AF7: Dout0Pin<USIC0_CH1>},
     P2_11 => {AF7: Dout0Pin<USIC0_CH1>}
 }
 
+macro_rules! hw_pins {
+    ($($(#[$attr:meta])* $PIN:ident => {
+        $($HW:ty: $TRAIT:ty),+
+    }),+) => {
+        $(
+            $(#[$attr])*
+            const _: () = {
+                $(
+                    impl $TRAIT for $PIN<HwControlled<$HW>> {}
+                )+
+            };
+        )+
+    }
+}
+
+// Data lines of dual and quad SPI, the direction is switched by the USIC
+hw_pins! {
+    #[cfg(not(feature = "t016"))]
+    P1_0 => {HW0: Dout0Pin<USIC0_CH0>},
+    #[cfg(not(feature = "t016"))]
+    P1_1 => {HW0: Dout1Pin<USIC0_CH0>},
+    #[cfg(not(feature = "t016"))]
+    P1_2 => {HW0: Dout2Pin<USIC0_CH0>},
+    #[cfg(not(feature = "t016"))]
+    P1_3 => {HW0: Dout3Pin<USIC0_CH0>}
+}
+
 macro_rules! input_pins_usic {
-    ($($PIN:ident => {
+    ($($(#[$attr:meta])* $PIN:ident => {
         $($USIC:ident => $DxX:ident: $chan:expr),+
     }),+) => {
         $(
-            $(
-                impl<STATE> $DxX<$USIC> for $PIN<Input<STATE>> {
-                    fn number() -> u8 {
-                        $chan
+            $(#[$attr])*
+            const _: () = {
+                $(
+                    impl<STATE> $DxX<$USIC> for $PIN<Input<STATE>> {
+                        fn number() -> u8 {
+                            $chan
+                        }
                     }
-                }
-            )+
+                )+
+            };
         )+
     }
 }
@@ -64,9 +106,66 @@ input_pins_usic! {
         USIC0_CH1 => Dx0Pin: 3,
         USIC0_CH1 => Dx1Pin: 2
     },
+    #[cfg(not(feature = "t016"))]
     P2_2 => {
         USIC0_CH0 => Dx3Pin: 0,
         USIC0_CH0 => Dx4Pin: 0,
         USIC0_CH0 => Dx5Pin: 0
     }
 }
+
+macro_rules! eru_pins {
+    ($($(#[$attr:meta])* $PIN:ident => ($channel:ident, $input:ident, $source:expr)),+) => {
+        $(
+            $(#[$attr])*
+            impl<STATE> ExtiPin for $PIN<Input<STATE>> {
+                type Channel = $channel;
+
+                fn eru_input() -> EruInput {
+                    EruInput {
+                        input: ErsInput::$input,
+                        source: $source,
+                    }
+                }
+            }
+        )+
+    }
+}
+
+// Pins connected to ERU0.xA0
+eru_pins! {
+    P2_0 => (CH0, A, 0),
+    #[cfg(not(feature = "t016"))]
+    P2_1 => (CH1, A, 0),
+    #[cfg(not(feature = "t016"))]
+    P2_2 => (CH2, A, 0),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P2_3 => (CH3, A, 0)
+}
+
+// Pins connected to ERU0.xA1
+eru_pins! {
+    P2_6 => (CH0, A, 1),
+    P2_7 => (CH1, A, 1),
+    P2_9 => (CH2, A, 1),
+    P2_10 => (CH3, A, 1)
+}
+
+// Pins connected to ERU0.xB0
+eru_pins! {
+    P0_0 => (CH0, B, 0),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_1 => (CH1, B, 0),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_2 => (CH2, B, 0),
+    #[cfg(not(any(feature = "t016", feature = "q024")))]
+    P0_3 => (CH3, B, 0)
+}
+
+// Pins connected to ERU0.xB1
+eru_pins! {
+    P0_5 => (CH0, B, 1),
+    P0_6 => (CH1, B, 1),
+    P0_7 => (CH2, B, 1),
+    P0_8 => (CH3, B, 1)
+}
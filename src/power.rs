@@ -0,0 +1,102 @@
+//! API for the power saving modes
+//!
+//! Both modes are entered with `WFI` and left on the next enabled interrupt.
+//! In deep sleep only peripherals which keep their clock (and the ERU, which
+//! isn't clock gated) can generate the wakeup interrupt.
+//!
+//! The behaviour of each pin during deep sleep is selected with
+//! `set_sleep_mode` on the pin, the port applies it while the chip is in deep
+//! sleep.
+
+use cortex_m::asm;
+use cortex_m::interrupt;
+use cortex_m::peripheral::SCB;
+
+use crate::scu::{unprotected, PeripheralClock, Scu, ALL_GATES};
+
+/// Enters sleep mode until the next interrupt
+///
+/// All clocks keep running
+pub fn sleep(scb: &mut SCB) {
+    scb.clear_sleepdeep();
+    asm::wfi();
+}
+
+/// Deep sleep configuration
+pub struct DeepSleep {
+    keep: u32,
+    flash_power_down: bool,
+}
+
+impl DeepSleep {
+    /// Gates all peripheral clocks and keeps the flash powered by default
+    pub fn new() -> Self {
+        DeepSleep {
+            keep: 0,
+            flash_power_down: false,
+        }
+    }
+
+    /// Keeps the clock of a peripheral running during deep sleep
+    ///
+    /// This only keeps clocks that are already enabled
+    pub fn keep_clock<P: PeripheralClock>(mut self) -> Self {
+        self.keep |= P::GATE;
+        self
+    }
+
+    /// Powers down the flash during deep sleep
+    ///
+    /// This saves more power, but increases the wakeup time
+    pub fn flash_power_down(mut self) -> Self {
+        self.flash_power_down = true;
+        self
+    }
+
+    /// Enters deep sleep until the next interrupt
+    ///
+    /// Pins set to `SleepMode::PowerSave` are put in power save mode until
+    /// wakeup.
+    ///
+    /// Peripheral clocks which were gated for deep sleep are enabled again on
+    /// wakeup. MCLK returns to the configured frequency, so `scu.clocks` stays
+    /// valid.
+    ///
+    /// Interrupts are masked while sleeping, a pending interrupt still wakes
+    /// the chip up. Its handler runs after the clocks have been restored.
+    pub fn enter(&self, scb: &mut SCB, scu: &mut Scu) {
+        let scu_clk = &scu.scu_clk;
+        let flash_power_down = self.flash_power_down;
+        interrupt::free(|_| {
+            let enabled = !scu_clk.cgatstat0.read().bits() & ALL_GATES;
+            let gate = enabled & !self.keep;
+            unprotected(|| {
+                // NOTE(unsafe) Only valid gating bits are written
+                scu_clk.cgatset0.write(|w| unsafe { w.bits(gate) });
+                scu_clk.pwrsvcr.write(|w| w.fpd().bit(flash_power_down));
+            });
+
+            scb.set_sleepdeep();
+            asm::dsb();
+            asm::wfi();
+            scb.clear_sleepdeep();
+
+            unprotected(|| {
+                scu_clk.pwrsvcr.write(|w| w.fpd().clear_bit());
+                // NOTE(unsafe) Only valid gating bits are written
+                scu_clk.cgatclr0.write(|w| unsafe { w.bits(gate) });
+            });
+            // Wait until the core voltage has settled again
+            while {
+                let clkcr = scu_clk.clkcr.read();
+                clkcr.vddc2low().bit_is_set() || clkcr.vddc2high().bit_is_set()
+            } {}
+        });
+    }
+}
+
+impl Default for DeepSleep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
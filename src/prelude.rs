@@ -1,3 +1,4 @@
+pub use crate::eru::EruExt as _xmc1100_hal_eru_EruExt;
 pub use crate::gpio::GpioExt as _xmc1100_hal_gpio_GpioExt;
 pub use embedded_hal::digital::v2::InputPin as _embedded_hal_gpio_InputPin;
 pub use embedded_hal::digital::v2::OutputPin as _embedded_hal_gpio_OutputPin;
@@ -1,77 +1,524 @@
 //! Rcc Handling
+use cortex_m::interrupt;
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::SYST;
+use xmc1100::SCU_ANALOG;
 use xmc1100::SCU_CLK;
 use xmc1100::SCU_GENERAL;
+use xmc1100::SCU_INTERRUPT;
+use xmc1100::SCU_RESET;
 
 // TODO This is mostly a shim. Port more from stm32f0xx-hal
 
+use crate::temperature::TemperatureSensor;
 use crate::time::{Hertz, MegaHertz};
 
+/// Frequency of the DCO1 oscillator after the fixed divide by 2
+const DCO1_HALF: u32 = 32_000_000;
+
+// Factory calibration data in the flash configuration sector
+/// Temperature of the first DCO1 calibration point in °C
+const ANA_TSE_T1: *const u8 = 0x1000_0F30 as *const u8;
+/// Temperature of the second DCO1 calibration point in °C
+const ANA_TSE_T2: *const u8 = 0x1000_0F31 as *const u8;
+/// ADJL offset of DCO1 at the first calibration point
+const DCO_ADJLO_T1: *const u8 = 0x1000_0F38 as *const u8;
+/// ADJL offset of DCO1 at the second calibration point
+const DCO_ADJLO_T2: *const u8 = 0x1000_0F39 as *const u8;
+
+/// Typical frequency change of DCO1 per ADJL offset step in ppm
+const ADJL_STEP_PPM: i64 = 9375;
+/// Largest ADJL offset of DCO1
+const ADJL_OFFSET_MAX: i32 = 8;
+
 /// Constrained SCU peripheral
 pub struct Scu {
     pub clocks: Clocks,
-    pub(crate) _scu_general: SCU_GENERAL,
+    /// Configured MCLK, which the DCO1 corrections aim for
+    nominal_mclk: Hertz,
+    /// Whether a `TemperatureSensor` exists
+    pub(crate) temperature_sensor_taken: bool,
+    pub(crate) scu_general: SCU_GENERAL,
     pub(crate) scu_clk: SCU_CLK,
 }
 
 impl Scu {
     pub fn new(scu_general: SCU_GENERAL, scu_clk: SCU_CLK) -> ClockConfig {
-        // Disable write protection
+        // Enable write protection, protected bits are only unlocked when needed
         scu_general
             .passwd
-            .write(|w| w.pass().value1().mode().value1());
+            .write(|w| w.pass().value1().mode().value2());
         let scu = Scu {
             clocks: Clocks {
-                sysclk: MegaHertz(8).into(),
+                mclk: MegaHertz(8).into(),
+                pclk: MegaHertz(8).into(),
             },
-            _scu_general: scu_general,
+            nominal_mclk: MegaHertz(8).into(),
+            temperature_sensor_taken: false,
+            scu_general,
             scu_clk,
         };
-        ClockConfig { scu, sysclk: None }
+        ClockConfig {
+            scu,
+            mclk: None,
+            pclk_double: false,
+            calibrate_dco: false,
+        }
+    }
+
+    /// Temporarily unlocks the protected SCU bits while running `f`
+    ///
+    /// The protected bits are locked again afterwards
+    pub fn unprotected<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&SCU_CLK) -> R,
+    {
+        let scu_clk = &self.scu_clk;
+        unprotected(|| f(scu_clk))
+    }
+
+    /// Returns the reason of the last reset
+    ///
+    /// The status accumulates until it's cleared with `clear_reset_reason`,
+    /// so the most specific reason is reported
+    pub fn reset_reason(&self) -> ResetReason {
+        // NOTE(unsafe) atomic read with no side effects
+        let status = unsafe { (*SCU_RESET::ptr()).rststat.read().rststat().bits() };
+        [
+            (1 << 5, ResetReason::Watchdog),
+            (1 << 3, ResetReason::Lockup),
+            (1 << 7, ResetReason::Parity),
+            (1 << 4, ResetReason::FlashEcc),
+            (1 << 6, ResetReason::ClockLoss),
+            (1 << 2, ResetReason::Software),
+            (1 << 1, ResetReason::Master),
+            (1 << 0, ResetReason::PowerOn),
+        ]
+        .iter()
+        .find(|(mask, _)| status & mask != 0)
+        .map(|(_, reason)| *reason)
+        .unwrap_or(ResetReason::Unknown)
+    }
+
+    /// Clears the reset status
+    pub fn clear_reset_reason(&mut self) {
+        // NOTE(unsafe) write to a stateless register of the SCU owned by `self`
+        unsafe { (*SCU_RESET::ptr()).rstclr.write(|w| w.rsclr().set_bit()) };
+    }
+
+    /// Resets the chip with a master reset request
+    ///
+    /// After the reset, this is reported as `ResetReason::Master`
+    pub fn software_reset(&mut self) -> ! {
+        unprotected(|| {
+            // NOTE(unsafe) The reset block is part of the SCU owned by `self`
+            unsafe {
+                (*SCU_RESET::ptr())
+                    .rstcon
+                    .modify(|_, w| w.mrsten().set_bit())
+            };
+        });
+        loop {
+            continue;
+        }
+    }
+
+    /// Returns the raw SCU service request flags (`SRRAW`)
+    ///
+    /// These are set regardless of the service request mask
+    pub fn raw_service_requests(&self) -> u32 {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*SCU_INTERRUPT::ptr()).srraw.read().bits() }
+    }
+
+    /// Clears the SCU service request flags set in `mask`
+    pub fn clear_service_requests(&mut self, mask: u32) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe { (*SCU_INTERRUPT::ptr()).srclr.write(|w| w.bits(mask)) };
+    }
+
+    /// Ungates the clock of a peripheral
+    pub fn enable_clock<P: PeripheralClock>(&mut self) {
+        P::enable(&self.scu_clk);
+    }
+
+    /// Gates the clock of a peripheral to save power
+    ///
+    /// The peripheral can't be used until the clock is enabled again
+    pub fn disable_clock<P: PeripheralClock>(&mut self) {
+        P::disable(&self.scu_clk);
+    }
+
+    /// Returns true if the peripheral is clocked
+    pub fn is_clock_enabled<P: PeripheralClock>(&self) -> bool {
+        P::is_enabled(&self.scu_clk)
+    }
+
+    /// Corrects the DCO1 frequency for the current die temperature
+    ///
+    /// The internal oscillator drifts with temperature, so this should be
+    /// called periodically on long-running devices, e.g. from a timer
+    /// interrupt. The temperature is measured with `sensor`, a pending result
+    /// of it is discarded. `clocks` are reset to the configured MCLK, which the
+    /// calibration aims for.
+    pub fn calibrate_dco(&mut self, sensor: &mut TemperatureSensor) {
+        let temperature = sensor.read_blocking();
+        self.calibrate_dco_at(temperature);
+    }
+
+    /// Corrects the DCO1 frequency for an already measured die temperature in °C
+    ///
+    /// `clocks` are reset to the configured MCLK
+    pub fn calibrate_dco_at(&mut self, temperature: i32) {
+        // NOTE(unsafe) read-only access to the flash configuration sector
+        let (t1, t2, adjl1, adjl2) = unsafe {
+            (
+                i32::from(*ANA_TSE_T1),
+                i32::from(*ANA_TSE_T2),
+                i32::from(*DCO_ADJLO_T1),
+                i32::from(*DCO_ADJLO_T2),
+            )
+        };
+        // Linear interpolation between both calibration points, like XMCLib does
+        let offset = if t2 != t1 {
+            adjl1 + (temperature - t1) * (adjl2 - adjl1) / (t2 - t1)
+        } else {
+            adjl1
+        };
+        let nominal = self.nominal_mclk.0;
+        self.apply_adjl_offset(offset, nominal);
+    }
+
+    /// Trims DCO1 towards the nominal MCLK using a measurement of the real MCLK
+    ///
+    /// The measurement can be done against any known reference, see
+    /// `measure_mclk`. As the trim only has a coarse resolution, the remaining
+    /// error is put into `clocks`. Peripherals which were already configured
+    /// have to be updated with the returned clocks, e.g. with
+    /// `Serial::set_baudrate` or `Timer::update_clocks`.
+    ///
+    /// Fails without changing the trim if the measurement is zero or further
+    /// off than the whole trim range, which points to a broken reference.
+    pub fn trim_dco(&mut self, measured_mclk: Hertz) -> Result<Clocks, ClockError> {
+        let nominal = i64::from(self.nominal_mclk.0);
+        let measured = i64::from(measured_mclk.0);
+        if measured == 0 {
+            return Err(ClockError::ImplausibleMeasurement {
+                measured: measured_mclk,
+            });
+        }
+        let error_ppm = (nominal - measured) * 1_000_000 / measured;
+        // Round to the nearest step
+        let steps = (error_ppm + error_ppm.signum() * ADJL_STEP_PPM / 2) / ADJL_STEP_PPM;
+        let max_steps = i64::from(ADJL_OFFSET_MAX);
+        if steps.abs() > max_steps {
+            return Err(ClockError::ImplausibleMeasurement {
+                measured: measured_mclk,
+            });
+        }
+        // NOTE(unsafe) read-only access to a register of the SCU owned by `self`
+        let current =
+            i32::from(unsafe { (*SCU_ANALOG::ptr()).anaoffset.read().adjl_offset().bits() });
+        // The steps are within the trim range, so they fit into an i32
+        let offset = (current + steps as i32).clamp(0, ADJL_OFFSET_MAX);
+        let applied = i64::from(offset - current);
+        // Estimate the resulting frequency
+        let mclk = (measured * (1_000_000 + applied * ADJL_STEP_PPM) / 1_000_000) as u32;
+        self.apply_adjl_offset(offset, mclk);
+        Ok(self.clocks)
     }
+
+    /// Sets the ADJL offset of DCO1 and the resulting MCLK in `clocks` together
+    fn apply_adjl_offset(&mut self, offset: i32, mclk: u32) {
+        set_adjl_offset(offset);
+        let pclk_double = self.scu_clk.clkcr.read().pclksel().bit_is_set();
+        self.clocks.mclk = Hertz(mclk);
+        self.clocks.pclk = Hertz(if pclk_double { mclk * 2 } else { mclk });
+    }
+}
+
+/// Measures the real MCLK frequency against a reference signal of known frequency
+///
+/// `wait_for_edge` has to block until the next edge (or period) of the
+/// reference, e.g. of the 32.768 kHz standby clock, an external pulse captured
+/// by CCU4 or the bits of a known UART sync byte. MCLK is counted with SysTick
+/// over `periods` periods of the reference, so the measurement has to be
+/// shorter than 2^24 MCLK cycles.
+pub fn measure_mclk<F>(
+    syst: &mut SYST,
+    reference: Hertz,
+    periods: u32,
+    mut wait_for_edge: F,
+) -> Hertz
+where
+    F: FnMut(),
+{
+    const SYSTICK_RANGE: u32 = 0x0100_0000;
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(SYSTICK_RANGE - 1);
+    syst.clear_current();
+    syst.enable_counter();
+    wait_for_edge();
+    let start = SYST::get_current();
+    for _ in 0..periods {
+        wait_for_edge();
+    }
+    let end = SYST::get_current();
+    // SysTick counts down
+    let ticks = start.wrapping_sub(end) % SYSTICK_RANGE;
+    Hertz((u64::from(ticks) * u64::from(reference.0) / u64::from(periods.max(1))) as u32)
+}
+
+/// Sets the ADJL offset of DCO1, clamped to the valid range
+fn set_adjl_offset(offset: i32) {
+    let offset = offset.clamp(0, ADJL_OFFSET_MAX);
+    // NOTE(unsafe) The analog block is part of the SCU
+    unprotected(|| unsafe {
+        (*SCU_ANALOG::ptr())
+            .anaoffset
+            .write(|w| w.adjl_offset().bits(offset as u8))
+    });
+}
+
+/// Runs `f` with the protected SCU bits unlocked
+///
+/// Nested calls keep the bits unlocked until the outermost call returns
+pub(crate) fn unprotected<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    interrupt::free(|_| {
+        // NOTE(unsafe) Only the password register is accessed, inside a critical section
+        let passwd = unsafe { &(*SCU_GENERAL::ptr()).passwd };
+        let locked = passwd.read().prots().bit_is_set();
+        if locked {
+            passwd.write(|w| w.pass().value2());
+            while passwd.read().prots().bit_is_set() {}
+        }
+        let result = f();
+        if locked {
+            passwd.write(|w| w.pass().value3());
+        }
+        result
+    })
 }
 
 pub struct ClockConfig {
     scu: Scu,
-    sysclk: Option<u32>,
+    mclk: Option<u32>,
+    pclk_double: bool,
+    calibrate_dco: bool,
 }
 
 impl ClockConfig {
-    pub fn sysclk<F>(mut self, freq: F) -> Self
+    /// Sets the main clock (MCLK) frequency
+    ///
+    /// MCLK is derived from 32 MHz with the integer (IDIV) and fractional
+    /// (FDIV) divider, so it can be set in steps of 1/256 of the divider
+    pub fn mclk<F>(mut self, freq: F) -> Self
     where
         F: Into<Hertz>,
     {
-        self.sysclk = Some(freq.into().0);
+        self.mclk = Some(freq.into().0);
         self
     }
 
-    pub fn freeze(mut self) -> Scu {
-        // TODO Do temperature calibration
-        if let Some(sysclk) = self.sysclk {
-            let idiv = 32000000 / sysclk;
-            if idiv > 0xFF || idiv == 0 {
-                panic!("Divider for sysclk invalid");
-            }
-            unsafe { self.scu.scu_clk.clkcr.write(|w| w.idiv().bits(idiv as u8)) };
-            // Calculate real frequency
-            self.scu.clocks.sysclk = Hertz(32000000 / idiv);
-        } else {
-            // Set default frequency of 8MHz
-            unsafe { self.scu.scu_clk.clkcr.write(|w| w.idiv().bits(0x04)) };
+    /// Sets the system (core) frequency, which is the same as MCLK
+    pub fn sysclk<F>(self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.mclk(freq)
+    }
+
+    /// Runs the peripheral clock (PCLK) at twice the MCLK frequency
+    ///
+    /// PCLK only clocks the CCU4, the USIC runs from MCLK
+    pub fn pclk_double(mut self) -> Self {
+        self.pclk_double = true;
+        self
+    }
+
+    /// Corrects DCO1 for the die temperature when freezing
+    ///
+    /// See `Scu::calibrate_dco`
+    pub fn calibrate_dco(mut self) -> Self {
+        self.calibrate_dco = true;
+        self
+    }
+
+    /// Applies the clock configuration
+    ///
+    /// # Panics
+    ///
+    /// Panics if the requested MCLK can't be reached, see `try_freeze`
+    pub fn freeze(self) -> Scu {
+        match self.try_freeze() {
+            Ok(scu) => scu,
+            Err((_, error)) => panic!("Divider for mclk invalid: {:?}", error),
+        }
+    }
+
+    /// Applies the clock configuration
+    ///
+    /// If the requested MCLK can't be reached, the unchanged configuration is
+    /// returned together with the closest achievable frequency
+    pub fn try_freeze(mut self) -> Result<Scu, (Self, ClockError)> {
+        // The default frequency is 8MHz
+        let mclk = self.mclk.unwrap_or(8_000_000);
+        // MCLK = 32MHz / (IDIV + FDIV / 256)
+        let div = (u64::from(DCO1_HALF) * 256 + u64::from(mclk) / 2) / u64::from(mclk.max(1));
+        if div < 0x100 {
+            let closest = Hertz(DCO1_HALF);
+            return Err((self, ClockError::InvalidMclk { closest }));
+        } else if div > 0xFFFF {
+            let closest = Hertz((u64::from(DCO1_HALF) * 256 / 0xFFFF) as u32);
+            return Err((self, ClockError::InvalidMclk { closest }));
+        }
+        let idiv = div >> 8;
+        let fdiv = div & 0xFF;
+        let pclk_double = self.pclk_double;
+        let scu_clk = &self.scu.scu_clk;
+        unprotected(|| unsafe {
+            scu_clk.clkcr.write(|w| {
+                w.idiv()
+                    .bits(idiv as u8)
+                    .fdiv()
+                    .bits(fdiv as u8)
+                    .pclksel()
+                    .bit(pclk_double)
+                    .cntadj()
+                    .bits(0x3FF)
+            })
+        });
+        // Wait until the core voltage has settled after the frequency change
+        while {
+            let clkcr = self.scu.scu_clk.clkcr.read();
+            clkcr.vddc2low().bit_is_set() || clkcr.vddc2high().bit_is_set()
+        } {}
+        // Calculate real frequency
+        let mclk = (u64::from(DCO1_HALF) * 256 / div) as u32;
+        self.scu.nominal_mclk = Hertz(mclk);
+        self.scu.clocks.mclk = Hertz(mclk);
+        self.scu.clocks.pclk = Hertz(if pclk_double { mclk * 2 } else { mclk });
+        if self.calibrate_dco {
+            // No `TemperatureSensor` can exist before the clocks are frozen
+            let temperature = crate::temperature::measure();
+            self.scu.calibrate_dco_at(temperature);
         }
-        self.scu
+        Ok(self.scu)
     }
 }
+
+/// Reason of the last reset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResetReason {
+    /// Power-on reset
+    ///
+    /// The XMC1100 also reports a brown-out reset like this
+    PowerOn,
+    /// Master reset, see `Scu::software_reset`
+    Master,
+    /// Software reset by the cpu (`SYSRESETREQ`)
+    Software,
+    /// Cpu lockup reset
+    Lockup,
+    /// Flash double bit ECC error reset
+    FlashEcc,
+    /// Watchdog reset
+    Watchdog,
+    /// Loss of clock reset
+    ClockLoss,
+    /// SRAM or USIC0 memory parity error reset
+    Parity,
+    /// No reset status is set, e.g. because it has been cleared
+    Unknown,
+}
+
+/// Peripheral with a clock gate in the SCU
+///
+/// The PRNG of the XMC1100 is always clocked and can't be gated
+pub trait PeripheralClock {
+    /// Bit of the peripheral in the clock gating registers
+    const GATE: u32;
+    /// Ungates the clock of the peripheral
+    fn enable(scu_clk: &SCU_CLK);
+    /// Gates the clock of the peripheral
+    fn disable(scu_clk: &SCU_CLK);
+    /// Returns true if the peripheral is clocked
+    fn is_enabled(scu_clk: &SCU_CLK) -> bool;
+}
+
+macro_rules! peripheral_clock {
+    ($($PERIPH:ident: ($field:ident, $bit:expr),)+) => {
+        $(
+            impl PeripheralClock for xmc1100::$PERIPH {
+                const GATE: u32 = 1 << $bit;
+
+                fn enable(scu_clk: &SCU_CLK) {
+                    unprotected(|| scu_clk.cgatclr0.write(|w| w.$field().set_bit()));
+                }
+
+                fn disable(scu_clk: &SCU_CLK) {
+                    unprotected(|| scu_clk.cgatset0.write(|w| w.$field().set_bit()));
+                }
+
+                fn is_enabled(scu_clk: &SCU_CLK) -> bool {
+                    scu_clk.cgatstat0.read().$field().bit_is_clear()
+                }
+            }
+        )+
+    }
+}
+
+peripheral_clock! {
+    USIC0: (usic0, 3),
+    CCU40: (ccu40, 2),
+    VADC: (vadc, 0),
+    WDT: (wdt, 9),
+    RTC: (rtc, 10),
+}
+
+/// All peripheral clock gates
+pub(crate) const ALL_GATES: u32 = (1 << 0) | (1 << 2) | (1 << 3) | (1 << 9) | (1 << 10);
+
+/// Clock configuration error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockError {
+    /// The requested MCLK can't be generated by the dividers
+    InvalidMclk {
+        /// Closest achievable MCLK
+        closest: Hertz,
+    },
+    /// The measured MCLK is zero or can't be corrected by trimming DCO1
+    ImplausibleMeasurement {
+        /// Measured MCLK
+        measured: Hertz,
+    },
+}
+
 /// Frozen clock frequencies
 ///
 /// The existence of this value indicates that the clock configuration can no longer be changed
 #[derive(Clone, Copy)]
 pub struct Clocks {
-    sysclk: Hertz,
+    mclk: Hertz,
+    pclk: Hertz,
 }
 
 impl Clocks {
     /// Returns the system (core) frequency
     pub fn sysclk(&self) -> Hertz {
-        self.sysclk
+        self.mclk
+    }
+
+    /// Returns the main clock (MCLK) frequency
+    pub fn mclk(&self) -> Hertz {
+        self.mclk
+    }
+
+    /// Returns the peripheral clock (PCLK) frequency
+    pub fn pclk(&self) -> Hertz {
+        self.pclk
     }
 }
@@ -1,6 +1,8 @@
 //! API for the integrated USIC ports
 //!
-//! This only implements the usual asynchronous bidirectional 8-bit transfers.
+//! This implements asynchronous bidirectional transfers, the frame format is
+//! selected with a `Config`. Words of more than 8 data bits are read and
+//! written with `read_word`/`write_word`.
 //!
 //! It's possible to use a read-only/write-only serial implementation with
 //! `usicXrx`/`usicXtx`.
@@ -14,7 +16,7 @@ use core::{
 use embedded_hal::prelude::*;
 
 use crate::usic::Dout0Pin;
-use crate::{scu::Scu, time::Bps, usic::*};
+use crate::{scu::Scu, time::Bps, usic::*, xmc1100::Interrupt};
 
 use core::marker::PhantomData;
 
@@ -33,6 +35,181 @@ pub enum Error {
     _Extensible,
 }
 
+/// Invalid serial configuration
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The oversampling isn't in the range of 4 to 32
+    Oversampling,
+    /// The sample point isn't less than the oversampling
+    SamplePoint,
+    /// The baud rate can't be generated from MCLK
+    Baudrate,
+}
+
+/// Interrupt events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// New data has been received
+    Receive,
+    /// The transmit buffer is empty
+    TransmitBufferEmpty,
+    /// The receive FIFO holds the given number of words (1 to 32)
+    ///
+    /// This replaces the `Receive` event
+    FifoLimit(u8),
+    /// A framing or noise error was detected, or received data was lost
+    ReceiveError,
+    /// A frame was finished, this fires after every received or transmitted
+    /// frame and doesn't detect an idle line
+    FrameFinished,
+}
+
+/// Service request line of USIC0, which triggers the interrupt `USIC0_x`
+///
+/// `ReceiveError` and `FrameFinished` share the protocol node pointer, so they're always
+/// routed to the same line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceRequest {
+    SR0,
+    SR1,
+    SR2,
+    SR3,
+    SR4,
+    SR5,
+}
+
+impl ServiceRequest {
+    fn number(self) -> u8 {
+        match self {
+            ServiceRequest::SR0 => 0,
+            ServiceRequest::SR1 => 1,
+            ServiceRequest::SR2 => 2,
+            ServiceRequest::SR3 => 3,
+            ServiceRequest::SR4 => 4,
+            ServiceRequest::SR5 => 5,
+        }
+    }
+
+    /// Returns the interrupt which is triggered by the service request line
+    pub fn interrupt(self) -> Interrupt {
+        match self {
+            ServiceRequest::SR0 => Interrupt::USIC0_0,
+            ServiceRequest::SR1 => Interrupt::USIC0_1,
+            ServiceRequest::SR2 => Interrupt::USIC0_2,
+            ServiceRequest::SR3 => Interrupt::USIC0_3,
+            ServiceRequest::SR4 => Interrupt::USIC0_4,
+            ServiceRequest::SR5 => Interrupt::USIC0_5,
+        }
+    }
+}
+
+/// Number of data bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordLength {
+    DataBits5,
+    DataBits6,
+    DataBits7,
+    DataBits8,
+    DataBits9,
+}
+
+/// Parity generation and checking
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parity {
+    ParityNone,
+    ParityEven,
+    ParityOdd,
+}
+
+/// Number of stop bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopBits {
+    /// 1 stop bit
+    STOP1,
+    /// 2 stop bits
+    STOP2,
+}
+
+/// Serial configuration
+///
+/// The default is 115200 baud 8N1 with an oversampling of 16
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub baudrate: Bps,
+    pub wordlength: WordLength,
+    pub parity: Parity,
+    pub stopbits: StopBits,
+    /// Number of time quanta per bit (4 to 32)
+    pub oversampling: u8,
+    /// Time quantum in which the bit is sampled, must be less than the
+    /// oversampling
+    ///
+    /// The majority of the three samples ending at the sample point is used.
+    /// Defaults to the middle of the bit (`oversampling / 2 + 1`)
+    pub sample_point: Option<u8>,
+}
+
+impl Config {
+    pub fn baudrate(mut self, baudrate: Bps) -> Self {
+        self.baudrate = baudrate;
+        self
+    }
+
+    pub fn wordlength(mut self, wordlength: WordLength) -> Self {
+        self.wordlength = wordlength;
+        self
+    }
+
+    pub fn parity_none(mut self) -> Self {
+        self.parity = Parity::ParityNone;
+        self
+    }
+
+    pub fn parity_even(mut self) -> Self {
+        self.parity = Parity::ParityEven;
+        self
+    }
+
+    pub fn parity_odd(mut self) -> Self {
+        self.parity = Parity::ParityOdd;
+        self
+    }
+
+    pub fn stopbits(mut self, stopbits: StopBits) -> Self {
+        self.stopbits = stopbits;
+        self
+    }
+
+    pub fn oversampling(mut self, oversampling: u8) -> Self {
+        self.oversampling = oversampling;
+        self
+    }
+
+    pub fn sample_point(mut self, sample_point: u8) -> Self {
+        self.sample_point = Some(sample_point);
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            baudrate: Bps(115_200),
+            wordlength: WordLength::DataBits8,
+            parity: Parity::ParityNone,
+            stopbits: StopBits::STOP1,
+            oversampling: 16,
+            sample_point: None,
+        }
+    }
+}
+
+impl From<Bps> for Config {
+    fn from(baudrate: Bps) -> Self {
+        Config::default().baudrate(baudrate)
+    }
+}
+
 /// Serial abstraction
 pub struct Serial<USIC, TXPIN, RXPIN> {
     usic: USIC,
@@ -67,14 +244,19 @@ macro_rules! serial {
                 RXPIN: Dx0Pin<$USIC>,
             {
                 /// Creates a new serial instance
-                pub fn $usic(usic: $USIC, pins: (TXPIN, RXPIN), baud_rate: Bps, scu: &mut Scu) -> Self {
+                pub fn $usic<C: Into<Config>>(
+                    usic: $USIC,
+                    pins: (TXPIN, RXPIN),
+                    config: C,
+                    scu: &mut Scu,
+                ) -> core::result::Result<Self, ConfigError> {
                     let pin_num = RXPIN::number();
                     let mut serial = Serial { usic, pins };
-                    serial.configure(baud_rate, scu);
+                    serial.configure(config.into(), scu)?;
                     // Set rx pin
                     serial.usic.dx0cr.write(|w| w.dsel().bits(pin_num));
                     // TODO Enable transmission and receiving
-                    serial
+                    Ok(serial)
                 }
             }
 
@@ -83,15 +265,20 @@ macro_rules! serial {
                 TXPIN: Dout0Pin<$USIC>,
             {
                 /// Creates a new tx-only serial instance
-                pub fn $usictx(usic: $USIC, txpin: TXPIN, baud_rate: Bps, scu: &mut Scu) -> Self {
+                pub fn $usictx<C: Into<Config>>(
+                    usic: $USIC,
+                    txpin: TXPIN,
+                    config: C,
+                    scu: &mut Scu,
+                ) -> core::result::Result<Self, ConfigError> {
                     let rxpin = ();
                     let mut serial = Serial {
                         usic,
                         pins: (txpin, rxpin),
                     };
-                    serial.configure(baud_rate, scu);
+                    serial.configure(config.into(), scu)?;
                     // TODO Enable transmission
-                    serial
+                    Ok(serial)
                 }
             }
 
@@ -100,7 +287,12 @@ macro_rules! serial {
                 RXPIN: Dx0Pin<$USIC>,
             {
                 /// Creates a new tx-only serial instance
-                pub fn $usicrx(usic: $USIC, rxpin: RXPIN, baud_rate: Bps, scu: &mut Scu) -> Self {
+                pub fn $usicrx<C: Into<Config>>(
+                    usic: $USIC,
+                    rxpin: RXPIN,
+                    config: C,
+                    scu: &mut Scu,
+                ) -> core::result::Result<Self, ConfigError> {
                     let txpin = ();
                     let pin_num = RXPIN::number();
                     // Set rx pin
@@ -108,19 +300,38 @@ macro_rules! serial {
                         usic,
                         pins: (txpin, rxpin),
                     };
-                    serial.configure(baud_rate, scu);
+                    serial.configure(config.into(), scu)?;
                     // Set rx pin
                     serial.usic.dx0cr.write(|w| w.dsel().bits(pin_num));
                     // TODO Enable receiving
-                    serial
+                    Ok(serial)
                 }
             }
 
             impl<TXPIN, RXPIN> Serial<$USIC, TXPIN, RXPIN> {
-                fn configure(&mut self, baud: Bps, scu: &mut Scu) {
+                fn configure(
+                    &mut self,
+                    config: Config,
+                    scu: &mut Scu,
+                ) -> core::result::Result<(), ConfigError> {
+                    if !(4..=32).contains(&config.oversampling) {
+                        return Err(ConfigError::Oversampling);
+                    }
+                    let sample_point = config
+                        .sample_point
+                        .unwrap_or(config.oversampling / 2 + 1);
+                    if sample_point >= config.oversampling {
+                        return Err(ConfigError::SamplePoint);
+                    }
+                    let data_bits = match config.wordlength {
+                        WordLength::DataBits5 => 5,
+                        WordLength::DataBits6 => 6,
+                        WordLength::DataBits7 => 7,
+                        WordLength::DataBits8 => 8,
+                        WordLength::DataBits9 => 9,
+                    };
                     // Disable clock gating
-                    scu.scu_clk.cgatclr0.write(|w| w.usic0().set_bit());
-                    // XMC 1100 with 115200 8 n
+                    scu.enable_clock::<crate::xmc1100::USIC0>();
                     // Enable module
                     self.usic
                         .kscfg
@@ -128,18 +339,28 @@ macro_rules! serial {
                     // Force a read, recommended by the datasheet
                     self.usic.kscfg.read();
 
-                    // Set the timing with oversampling of 16
-                    crate::usic::set_baudrate(&mut self.usic, scu, baud, 16).unwrap();
+                    // Set the timing
+                    crate::usic::set_baudrate(&self.usic, scu, config.baudrate, config.oversampling)
+                        .map_err(|_| ConfigError::Baudrate)?;
                     // USIC Shift Control
-                    // SCTR.FLE = 8 (Frame Length)
-                    // SCTR.WLE = 8 (Word Length)
+                    // SCTR.FLE = data bits (Frame Length)
+                    // SCTR.WLE = data bits (Word Length)
                     // SCTR.TRM = 1 (Transmission Mode)
                     // SCTR.PDL = 1 (This bit defines the output level at the shift data output
                     // signal when no data is available for transmission)
                     unsafe {
                         self.usic
                             .sctr
-                            .write(|w| w.pdl().set_bit().trm().value2().fle().bits(7).wle().bits(7))
+                            .write(|w| {
+                                w.pdl()
+                                    .set_bit()
+                                    .trm()
+                                    .value2()
+                                    .fle()
+                                    .bits(data_bits - 1)
+                                    .wle()
+                                    .bits(data_bits - 1)
+                            })
                     };
                     // Configuration of USIC Transmit Control/Status Register
                     // TBUF.TDEN = 1 (TBUF Data Enable: A transmission of the data word in TBUF
@@ -151,11 +372,22 @@ macro_rules! serial {
                         .write(|w| w.tdssm().set_bit().tden().bits(1));
                     // Configuration of Protocol Control Register
                     // PCR.SMD = 1 (Sample Mode based on majority)
-                    // PCR.STPB = 0 (1x Stop bit)
-                    // PCR.SP = 5 (Sample Point)
+                    // PCR.STPB = Stop bits
+                    // PCR.SP = Sample Point
                     // PCR.PL = 0 (Pulse Length is equal to the bit length)
+                    let two_stop_bits = match config.stopbits {
+                        StopBits::STOP1 => false,
+                        StopBits::STOP2 => true,
+                    };
                     unsafe {self.usic.pcr_ascmode_mut()
-                            .write(|w| w.smd().set_bit().sp().bits(9))
+                            .write(|w| {
+                                w.smd()
+                                    .set_bit()
+                                    .stpb()
+                                    .bit(two_stop_bits)
+                                    .sp()
+                                    .bits(sample_point)
+                            })
                     };
                     // Configure Transmit Buffer
                     // Standard transmit buffer event is enabled
@@ -171,9 +403,17 @@ macro_rules! serial {
                         .rbctr
                         .write(|w| w.size().value6().dptr().bits(32)) };
                     // Configuration of Channel Control Register
-                    // CCR.PM = 00 ( Disable parity generation)
+                    // CCR.PM = Parity generation
                     // CCR.MODE = 2 (ASC mode enabled. Note: 0 (USIC channel is disabled))
-                    self.usic.ccr.write(|w| w.mode().value3().pm().value1());
+                    self.usic.ccr.write(|w| {
+                        let w = w.mode().value3();
+                        match config.parity {
+                            Parity::ParityNone => w.pm().value1(),
+                            Parity::ParityEven => w.pm().value3(),
+                            Parity::ParityOdd => w.pm().value4(),
+                        }
+                    });
+                    Ok(())
                 }
             }
         )+
@@ -185,6 +425,26 @@ serial! {
     USIC0_CH1: (usic0_ch1, usic0_ch1tx, usic0_ch0rx),
 }
 
+impl<USIC> Rx<USIC> {
+    /// Tries to read a word, which can be longer than 8 bits
+    pub fn read_word(&mut self) -> nb::Result<u16, Error> {
+        read(self.usic)
+    }
+
+    /// Clears all receive error flags without reporting them
+    pub fn clear_errors(&mut self) {
+        clear_errors(self.usic)
+    }
+}
+
+impl<USIC> Tx<USIC> {
+    /// Tries to write a word, which can be longer than 8 bits
+    /// Fails if the transmit buffer is full
+    pub fn write_word(&mut self, word: u16) -> nb::Result<(), void::Void> {
+        write(self.usic, word)
+    }
+}
+
 impl<USIC> embedded_hal::serial::Read<u8> for Rx<USIC>
 where
     USIC: Deref<Target = UsicRegisterBlock>,
@@ -193,7 +453,7 @@ where
 
     /// Tries to read a byte from the uart
     fn read(&mut self) -> nb::Result<u8, Error> {
-        read(self.usic)
+        read(self.usic).map(|word| word as u8)
     }
 }
 
@@ -206,7 +466,7 @@ where
 
     /// Tries to read a byte from the uart
     fn read(&mut self) -> nb::Result<u8, Error> {
-        read(&*self.usic)
+        read(&*self.usic).map(|word| word as u8)
     }
 }
 
@@ -224,7 +484,7 @@ where
     /// Tries to write a byte to the uart
     /// Fails if the transmit buffer is full
     fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
-        write(self.usic, byte)
+        write(self.usic, u16::from(byte))
     }
 }
 
@@ -243,7 +503,7 @@ where
     /// Tries to write a byte to the uart
     /// Fails if the transmit buffer is full
     fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
-        write(&*self.usic, byte)
+        write(&*self.usic, u16::from(byte))
     }
 }
 
@@ -270,7 +530,129 @@ where
         )
     }
 
-    pub fn release(self) -> (USIC, (TXPIN, RXPIN)) {
+    /// Tries to read a word, which can be longer than 8 bits
+    pub fn read_word(&mut self) -> nb::Result<u16, Error> {
+        read(&*self.usic)
+    }
+
+    /// Tries to write a word, which can be longer than 8 bits
+    /// Fails if the transmit buffer is full
+    pub fn write_word(&mut self, word: u16) -> nb::Result<(), void::Void> {
+        write(&*self.usic, word)
+    }
+
+    /// Clears all receive error flags without reporting them
+    pub fn clear_errors(&mut self) {
+        clear_errors(&*self.usic)
+    }
+
+    /// Starts listening for an `event` on the service request line `sr`
+    pub fn listen(&mut self, event: &Event, sr: ServiceRequest) {
+        let node = sr.number();
+        // NOTE(unsafe) the node pointers and limits are in range
+        match *event {
+            Event::Receive => unsafe {
+                self.usic.rbctr.modify(|_, w| {
+                    w.limit()
+                        .bits(0)
+                        .lof()
+                        .set_bit()
+                        .srbinp()
+                        .bits(node)
+                        .srbien()
+                        .set_bit()
+                })
+            },
+            Event::FifoLimit(words) => {
+                assert!((1..=32).contains(&words), "FIFO limit invalid");
+                unsafe {
+                    self.usic.rbctr.modify(|_, w| {
+                        w.limit()
+                            .bits(words - 1)
+                            .lof()
+                            .set_bit()
+                            .srbinp()
+                            .bits(node)
+                            .srbien()
+                            .set_bit()
+                    })
+                }
+            }
+            Event::TransmitBufferEmpty => unsafe {
+                // The event is triggered when the fill level falls below 1
+                self.usic.tbctr.modify(|_, w| {
+                    w.limit()
+                        .bits(1)
+                        .lof()
+                        .clear_bit()
+                        .stbinp()
+                        .bits(node)
+                        .stbien()
+                        .set_bit()
+                })
+            },
+            Event::ReceiveError => {
+                unsafe { self.usic.inpr.modify(|_, w| w.pinp().bits(node)) };
+                self.usic
+                    .pcr_ascmode()
+                    .modify(|_, w| w.rnien().set_bit().feien().set_bit());
+                self.usic.ccr.modify(|_, w| w.dlien().set_bit());
+            }
+            Event::FrameFinished => {
+                unsafe { self.usic.inpr.modify(|_, w| w.pinp().bits(node)) };
+                self.usic.pcr_ascmode().modify(|_, w| w.ffien().set_bit());
+            }
+        }
+    }
+
+    /// Stops listening for an `event`
+    pub fn unlisten(&mut self, event: &Event) {
+        match *event {
+            Event::Receive | Event::FifoLimit(_) => {
+                self.usic.rbctr.modify(|_, w| w.srbien().clear_bit())
+            }
+            Event::TransmitBufferEmpty => self.usic.tbctr.modify(|_, w| w.stbien().clear_bit()),
+            Event::ReceiveError => {
+                self.usic
+                    .pcr_ascmode()
+                    .modify(|_, w| w.rnien().clear_bit().feien().clear_bit());
+                self.usic.ccr.modify(|_, w| w.dlien().clear_bit());
+            }
+            Event::FrameFinished => self.usic.pcr_ascmode().modify(|_, w| w.ffien().clear_bit()),
+        }
+    }
+
+    /// Changes the baud rate, e.g. after the clocks have been trimmed
+    pub fn set_baudrate(
+        &mut self,
+        baud_rate: Bps,
+        scu: &Scu,
+    ) -> core::result::Result<(), ConfigError> {
+        // The channel has to be disabled while changing the timing
+        let oversampling = self.usic.brg.read().dctq().bits() + 1;
+        self.usic.ccr.modify(|_, w| w.mode().value1());
+        let result = crate::usic::set_baudrate(&self.usic, scu, baud_rate, oversampling)
+            .map_err(|_| ConfigError::Baudrate);
+        self.usic.ccr.modify(|_, w| w.mode().value3());
+        result
+    }
+
+    /// Disables the usic channel and releases the peripheral and pins
+    ///
+    /// USIC0 is gated again if the other channel is disabled as well
+    pub fn release(self, scu: &mut Scu) -> (USIC, (TXPIN, RXPIN)) {
+        self.usic.ccr.write(|w| w.mode().value1());
+        self.usic
+            .kscfg
+            .write(|w| w.moden().clear_bit().bpmoden().set_bit());
+        // NOTE(unsafe) atomic read with no side effects
+        let in_use = unsafe {
+            (*USIC0_CH0::ptr()).kscfg.read().moden().bit_is_set()
+                || (*USIC0_CH1::ptr()).kscfg.read().moden().bit_is_set()
+        };
+        if !in_use {
+            scu.disable_clock::<crate::xmc1100::USIC0>();
+        }
         (self.usic, self.pins)
     }
 }
@@ -313,38 +695,68 @@ fn flush(usic: *const UsicRegisterBlock) -> nb::Result<(), void::Void> {
 
 /// Tries to write a byte to the UART
 /// Fails if the transmit buffer is full
-fn write(usic: *const UsicRegisterBlock, byte: u8) -> nb::Result<(), void::Void> {
+fn write(usic: *const UsicRegisterBlock, word: u16) -> nb::Result<(), void::Void> {
     // NOTE(unsafe) atomic read with no side effects
     let trbsr = unsafe { (*usic).trbsr.read() };
 
     if trbsr.tfull().bit_is_clear() {
         // Write into first fifo buffer
-        unsafe { (*usic).in_[0].write(|w| w.tdata().bits(byte as u16)) };
+        unsafe { (*usic).in_[0].write(|w| w.tdata().bits(word)) };
         Ok(())
     } else {
         Err(nb::Error::WouldBlock)
     }
 }
 
-/// Tries to read a byte from the UART
-fn read(usic: *const UsicRegisterBlock) -> nb::Result<u8, Error> {
+/// Clears the receive error flags
+fn clear_errors(usic: *const UsicRegisterBlock) {
+    // NOTE(unsafe) atomic write to a stateless register
+    unsafe {
+        (*usic).pscr.write(|w| {
+            // RNS, FER0 and FER1 in ASC mode
+            w.cst4()
+                .set_bit()
+                .cst5()
+                .set_bit()
+                .cst6()
+                .set_bit()
+                .cdlif()
+                .set_bit()
+        })
+    };
+}
+
+/// Tries to read a word from the UART
+///
+/// Error flags are cleared when they're reported, so the next read returns the
+/// following word
+fn read(usic: *const UsicRegisterBlock) -> nb::Result<u16, Error> {
     // NOTE(unsafe) atomic read with no side effects
     let trbsr = unsafe { (*usic).trbsr.read() };
     // NOTE(unsafe) atomic read with no side effects
     let psr = unsafe { &(*usic).psr_ascmode().read() };
-    Err(
-        // TODO Detect Parity error
-        if psr.fer0().bit_is_set() || psr.fer1().bit_is_set() {
-            nb::Error::Other(Error::Framing)
-        } else if psr.rns().bit_is_set() {
-            nb::Error::Other(Error::Noise)
-        } else if trbsr.rfull().bit_is_set() {
-            nb::Error::Other(Error::Overrun)
-        } else if trbsr.rempty().bit_is_clear() {
-            // NOTE(read_volatile) see `write_volatile` below
-            return Ok(unsafe { ptr::read_volatile(&(*usic).outr as *const _ as *const _) });
+    // NOTE(unsafe) atomic writes to a stateless register
+    let pscr = unsafe { &(*usic).pscr };
+    Err(if psr.fer0().bit_is_set() || psr.fer1().bit_is_set() {
+        pscr.write(|w| w.cst5().set_bit().cst6().set_bit());
+        nb::Error::Other(Error::Framing)
+    } else if psr.rns().bit_is_set() {
+        pscr.write(|w| w.cst4().set_bit());
+        nb::Error::Other(Error::Noise)
+    } else if psr.dlif().bit_is_set() {
+        // A word was lost, because the receive buffer was full
+        pscr.write(|w| w.cdlif().set_bit());
+        nb::Error::Other(Error::Overrun)
+    } else if trbsr.rempty().bit_is_clear() {
+        // NOTE(read_volatile) see `write_volatile` below
+        let outr: u32 = unsafe { ptr::read_volatile(&(*usic).outr as *const _ as *const _) };
+        // RCI[4] is the parity error flag of the received word in ASC mode
+        if outr & (1 << 20) != 0 {
+            nb::Error::Other(Error::Parity)
         } else {
-            nb::Error::WouldBlock
-        },
-    )
+            return Ok(outr as u16);
+        }
+    } else {
+        nb::Error::WouldBlock
+    })
 }
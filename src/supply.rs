@@ -0,0 +1,131 @@
+//! API for the supply voltage monitoring
+//!
+//! The VDEL detector compares VDDP against a threshold and raises the VDDP
+//! pre-warning event when it drops below. SCU events are serviced by the
+//! `SCU_0`..`SCU_2` interrupts, the supply events are serviced by `SCU_1`.
+
+use xmc1100::{SCU_ANALOG, SCU_INTERRUPT, SCU_POWER};
+
+use crate::scu::{unprotected, Scu};
+
+/// VDEL detection threshold
+#[derive(Clone, Copy)]
+pub enum Threshold {
+    /// 2.25V
+    V2_25,
+    /// 3.0V
+    V3_0,
+    /// 4.4V
+    V4_4,
+}
+
+/// Supply monitoring events
+pub enum Event {
+    /// VDDP fell below the VDEL threshold
+    VddpPrewarning,
+    /// VDDP dropped below the brown-out limit
+    VddpDrop,
+    /// The core voltage regulator is clipping
+    Vclip,
+}
+
+impl Event {
+    fn mask(&self) -> u32 {
+        match self {
+            Event::VddpPrewarning => 1 << 3,
+            Event::VddpDrop => 1 << 7,
+            Event::Vclip => 1 << 21,
+        }
+    }
+}
+
+/// VDEL supply monitor
+pub struct SupplyMonitor {
+    _private: (),
+}
+
+impl SupplyMonitor {
+    /// Enables the VDEL detector with the given `threshold`
+    pub fn new(_scu: &mut Scu, threshold: Threshold) -> Self {
+        let mut monitor = SupplyMonitor { _private: () };
+        monitor.set_threshold(threshold);
+        monitor
+    }
+
+    /// Changes the detection threshold
+    pub fn set_threshold(&mut self, threshold: Threshold) {
+        unprotected(|| {
+            // NOTE(unsafe) The analog block is part of the SCU
+            unsafe {
+                (*SCU_ANALOG::ptr()).anavdel.write(|w| {
+                    let w = match threshold {
+                        Threshold::V2_25 => w.vdel_select().value1(),
+                        Threshold::V3_0 => w.vdel_select().value2(),
+                        Threshold::V4_4 => w.vdel_select().value3(),
+                    };
+                    // Fastest response time, so there's as much time left as possible
+                    w.vdel_tim_adj().value4().vdel_en().set_bit()
+                })
+            };
+        });
+    }
+
+    /// Returns true if VDDP is currently below the threshold
+    pub fn is_undervoltage(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*SCU_POWER::ptr()).vdesr.read().vddppw().bit_is_set() }
+    }
+
+    /// Starts listening for an `event`
+    pub fn listen(&mut self, event: Event) {
+        interrupt_mask(|mask| mask | event.mask());
+    }
+
+    /// Stops listening for an `event`
+    pub fn unlisten(&mut self, event: Event) {
+        interrupt_mask(|mask| mask & !event.mask());
+    }
+
+    /// Returns true if the `event` occurred
+    pub fn is_pending(&self, event: Event) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*SCU_INTERRUPT::ptr()).srraw.read().bits() & event.mask() != 0 }
+    }
+
+    /// Clears the `event`
+    pub fn clear_pending(&mut self, event: Event) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe {
+            (*SCU_INTERRUPT::ptr())
+                .srclr
+                .write(|w| w.bits(event.mask()))
+        };
+    }
+
+    /// Disables the VDEL detector
+    pub fn free(self) {
+        unprotected(|| {
+            // NOTE(unsafe) The analog block is part of the SCU
+            unsafe {
+                (*SCU_ANALOG::ptr())
+                    .anavdel
+                    .modify(|_, w| w.vdel_en().clear_bit())
+            };
+        });
+    }
+}
+
+/// Modifies the SCU service request mask in a critical section
+fn interrupt_mask<F>(f: F)
+where
+    F: FnOnce(u32) -> u32,
+{
+    cortex_m::interrupt::free(|_| {
+        // NOTE(unsafe) The read-modify-write is done in a critical section
+        unsafe {
+            (*SCU_INTERRUPT::ptr())
+                .srmsk
+                .modify(|r, w| w.bits(f(r.bits())))
+        };
+    });
+}
@@ -0,0 +1,122 @@
+//! API for the on-chip die temperature sensor
+//!
+//! The sensor result is converted with the rom routine, which applies the
+//! factory calibration from the flash configuration sector.
+
+use void::Void;
+use xmc1100::{SCU_ANALOG, SCU_INTERRUPT};
+
+use crate::scu::Scu;
+
+/// Address of the `CalcTemperature` routine pointer in the rom
+///
+/// The routine converts the temperature sensor result to kelvin
+const ROM_CALC_TEMPERATURE: *const extern "C" fn() -> u32 = 0x0000_010C as *const _;
+
+/// Die temperature sensor
+///
+/// Only one instance exists at a time, as all of them would share the sensor
+/// and its status flag
+pub struct TemperatureSensor {
+    _private: (),
+}
+
+impl TemperatureSensor {
+    /// Powers up the temperature sensor and starts the first measurement
+    ///
+    /// Returns `None` if the sensor is already in use
+    pub fn new(scu: &mut Scu) -> Option<Self> {
+        if scu.temperature_sensor_taken {
+            return None;
+        }
+        scu.temperature_sensor_taken = true;
+        let mut sensor = TemperatureSensor { _private: () };
+        sensor.start();
+        // NOTE(unsafe) The analog block is part of the SCU
+        unsafe {
+            (*SCU_ANALOG::ptr())
+                .anatsectrl
+                .write(|w| w.tse_en().set_bit())
+        };
+        Some(sensor)
+    }
+
+    /// Starts a new measurement
+    ///
+    /// The result of a running measurement is discarded
+    pub fn start(&mut self) {
+        clear_done();
+    }
+
+    /// Returns the temperature in °C if a measurement is complete
+    ///
+    /// Each result is only returned once, the next `read` blocks until the
+    /// following measurement is complete
+    pub fn read(&mut self) -> nb::Result<i32, Void> {
+        if is_done() {
+            let temperature = celsius();
+            clear_done();
+            Ok(temperature)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Measures the temperature in °C
+    pub fn read_blocking(&mut self) -> i32 {
+        self.start();
+        match nb::block!(self.read()) {
+            Ok(temperature) => temperature,
+            Err(void) => match void {},
+        }
+    }
+
+    /// Powers down the temperature sensor, so it can be taken again
+    pub fn free(self, scu: &mut Scu) {
+        scu.temperature_sensor_taken = false;
+        // NOTE(unsafe) The analog block is part of the SCU
+        unsafe {
+            (*SCU_ANALOG::ptr())
+                .anatsectrl
+                .write(|w| w.tse_en().clear_bit())
+        };
+    }
+}
+
+/// Measures the die temperature in °C
+///
+/// The sensor is only powered during the measurement, so this must only be
+/// used while no `TemperatureSensor` exists
+pub(crate) fn measure() -> i32 {
+    // NOTE(unsafe) The analog block is part of the SCU
+    let anatsectrl = unsafe { &(*SCU_ANALOG::ptr()).anatsectrl };
+    clear_done();
+    anatsectrl.write(|w| w.tse_en().set_bit());
+    // Wait for a measurement to complete
+    while !is_done() {}
+    let temperature = celsius();
+    clear_done();
+    anatsectrl.write(|w| w.tse_en().clear_bit());
+    temperature
+}
+
+fn clear_done() {
+    // NOTE(unsafe) atomic write to a stateless register
+    unsafe {
+        (*SCU_INTERRUPT::ptr())
+            .srclr
+            .write(|w| w.tse_done().set_bit())
+    };
+}
+
+fn is_done() -> bool {
+    // NOTE(unsafe) atomic read with no side effects
+    unsafe { (*SCU_INTERRUPT::ptr()).srraw.read().tse_done().bit_is_set() }
+}
+
+/// Converts the last sensor result to °C
+fn celsius() -> i32 {
+    // NOTE(unsafe) The rom routine only reads the temperature sensor result
+    let kelvin = unsafe { (*ROM_CALC_TEMPERATURE)() };
+    kelvin as i32 - 273
+}
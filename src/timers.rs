@@ -5,7 +5,9 @@
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m::peripheral::SYST;
 
-use crate::scu::{Clocks, Scu};
+use crate::eru::{Channel, Ogu};
+use crate::gpio::Edge;
+use crate::scu::{Clocks, PeripheralClock, Scu};
 use crate::time::Hertz;
 use core::ops::Deref;
 use embedded_hal::timer::{CountDown, Periodic};
@@ -24,15 +26,60 @@ pub struct SystickTimer {
     clocks: Clocks,
     tim: SYST,
 }
+/// Timer error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimerError {
+    /// The timeout can't be reached by the timer
+    InvalidTimeout {
+        /// Closest achievable timeout
+        closest: Hertz,
+    },
+}
+
 /// Interrupt events
 pub enum Event {
     /// Timer timed out / count down ended
     TimeOut,
 }
 
+/// External event of a CCU4 slice
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExternalEvent {
+    Event0,
+    Event1,
+    Event2,
+}
+
+/// Slice function, which can be triggered by an external event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExternalFunction {
+    /// Starts the timer
+    Start,
+    /// Stops the timer
+    Stop,
+    /// Captures the timer value into the capture register 1
+    Capture0,
+    /// Captures the timer value into the capture register 3
+    Capture1,
+}
+
 impl SystickTimer {
     /// Configures the SYST clock as a periodic count down timer
-    pub fn syst<T>(mut syst: SYST, timeout: T, scu: &Scu) -> Self
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timeout can't be reached, see `try_syst`
+    pub fn syst<T>(syst: SYST, timeout: T, scu: &Scu) -> Self
+    where
+        T: Into<Hertz>,
+    {
+        Self::try_syst(syst, timeout, scu).expect("Timeout can't be reached")
+    }
+
+    /// Tries to configure the SYST clock as a periodic count down timer
+    ///
+    /// Fails with the closest achievable timeout if `timeout` can't be reached
+    pub fn try_syst<T>(mut syst: SYST, timeout: T, scu: &Scu) -> Result<Self, TimerError>
     where
         T: Into<Hertz>,
     {
@@ -41,8 +88,8 @@ impl SystickTimer {
             tim: syst,
             clocks: scu.clocks,
         };
-        timer.start(timeout);
-        timer
+        timer.try_start(timeout)?;
+        Ok(timer)
     }
 
     /// Starts listening for an `event`
@@ -58,6 +105,38 @@ impl SystickTimer {
             Event::TimeOut => self.tim.disable_interrupt(),
         }
     }
+
+    /// Uses updated clocks, e.g. after trimming
+    ///
+    /// Takes effect on the next `start`
+    pub fn update_clocks(&mut self, scu: &Scu) {
+        self.clocks = scu.clocks;
+    }
+
+    /// Tries to start the timer with a `timeout`
+    ///
+    /// Fails with the closest achievable timeout if `timeout` can't be reached
+    pub fn try_start<T>(&mut self, timeout: T) -> Result<(), TimerError>
+    where
+        T: Into<Hertz>,
+    {
+        let sysclk = self.clocks.sysclk().0;
+        let ticks = sysclk / timeout.into().0.max(1);
+        // The reload value has to be between 1 and 0x00FFFFFF
+        if ticks < 2 {
+            let closest = Hertz(sysclk / 2);
+            return Err(TimerError::InvalidTimeout { closest });
+        } else if ticks > (1 << 24) {
+            // Round up, so the closest timeout can be reached
+            let closest = Hertz((sysclk + (1 << 24) - 1) >> 24);
+            return Err(TimerError::InvalidTimeout { closest });
+        }
+
+        self.tim.set_reload(ticks - 1);
+        self.tim.clear_current();
+        self.tim.enable_counter();
+        Ok(())
+    }
 }
 
 /// Use the systick as a timer
@@ -67,17 +146,15 @@ impl CountDown for SystickTimer {
     type Time = Hertz;
 
     /// Start the timer with a `timeout`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timeout can't be reached, see `try_start`
     fn start<T>(&mut self, timeout: T)
     where
         T: Into<Hertz>,
     {
-        let rvr = self.clocks.sysclk().0 / timeout.into().0 - 1;
-
-        assert!(rvr < (1 << 24));
-
-        self.tim.set_reload(rvr);
-        self.tim.clear_current();
-        self.tim.enable_counter();
+        self.try_start(timeout).expect("Timeout can't be reached");
     }
 
     /// Return `Ok` if the timer has wrapped
@@ -98,14 +175,20 @@ impl Periodic for SystickTimer {}
 pub(crate) type CcuRegisterBlock = ccu40_cc40::RegisterBlock;
 
 pub trait CcuSliceGlobal {
+    /// Inputs of the slice connected to `ERU0.GOUT0` to `ERU0.GOUT3`
+    const ERU_GOUT_INPUTS: [u8; 4];
+
     fn reset(&self);
+    fn idle(&self);
     fn transfer_shadow(&self);
 }
 
 macro_rules! timer_trait {
-    ($($TIMER:ident: ($ssxi:ident, $csxi:ident, $sxse:ident)),+) => {
+    ($($TIMER:ident: ($ssxi:ident, $csxi:ident, $sxse:ident, $eru:expr)),+) => {
         $(
             impl CcuSliceGlobal for xmc1100::$TIMER {
+                const ERU_GOUT_INPUTS: [u8; 4] = $eru;
+
                 fn reset(&self) {
                     // Disable timer idle status
                     // NOTE(unsafe) This is a write only regist)er
@@ -114,6 +197,11 @@ macro_rules! timer_trait {
                     // NOTE(unsafe) This is a write only register
                     unsafe { (*CCU40::ptr()).gidlc.write(|w| w.$csxi().set_bit()) };
                 }
+                fn idle(&self) {
+                    // Put the timer into idle
+                    // NOTE(unsafe) This is a write only register
+                    unsafe { (*CCU40::ptr()).gidls.write(|w| w.$ssxi().set_bit()) };
+                }
                 fn transfer_shadow(&self) {
                     // Enable the timers
                     // NOTE(unsafe) This is a write only register
@@ -129,23 +217,41 @@ macro_rules! timer_trait {
     };
 }
 
+// ERU0 inputs of each slice according to the CCU40 interconnect table in the
+// XMC1100 reference manual (CCU4 chapter), input 0 is CCU40.INyA and 15 is
+// CCU40.INyP
 timer_trait!(
-    CCU40_CC40: (ss0i, cs0i, s0se),
-    CCU40_CC41: (ss1i, cs1i, s1se),
-    CCU40_CC42: (ss2i, cs2i, s2se),
-    CCU40_CC43: (ss3i, cs3i, s3se)
+    CCU40_CC40: (ss0i, cs0i, s0se, [2, 3, 4, 5]),
+    CCU40_CC41: (ss1i, cs1i, s1se, [2, 3, 4, 5]),
+    CCU40_CC42: (ss2i, cs2i, s2se, [2, 3, 4, 5]),
+    CCU40_CC43: (ss3i, cs3i, s3se, [2, 3, 4, 5])
 );
 
 impl<TIMER> Timer<TIMER>
 where
     TIMER: Deref<Target = CcuRegisterBlock> + CcuSliceGlobal,
 {
+    /// Configures a CCU4 slice as a periodic count down timer
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timeout can't be reached, see `try_timer`
     pub fn timer<T>(timer: TIMER, timeout: T, scu: &Scu) -> Self
+    where
+        T: Into<Hertz>,
+    {
+        Self::try_timer(timer, timeout, scu).expect("Timeout can't be reached")
+    }
+
+    /// Tries to configure a CCU4 slice as a periodic count down timer
+    ///
+    /// Fails with the closest achievable timeout if `timeout` can't be reached
+    pub fn try_timer<T>(timer: TIMER, timeout: T, scu: &Scu) -> Result<Self, TimerError>
     where
         T: Into<Hertz>,
     {
         // Disable clock gating
-        scu.scu_clk.cgatclr0.write(|w| w.ccu40().set_bit());
+        CCU40::enable(&scu.scu_clk);
         timer.reset();
         // Shadow Transfer on clear
         timer.tc.write(|w| w.clst().set_bit());
@@ -153,22 +259,43 @@ where
             tim: timer,
             clocks: scu.clocks,
         };
-        timer.start(timeout);
+        timer.try_start(timeout)?;
         // Start the timer
         timer.tim.tcset.write(|w| w.trbs().set_bit());
         unsafe { (*CCU40::ptr()).gidlc.write(|w| w.sprb().set_bit()) };
-        timer
+        Ok(timer)
     }
-}
 
-impl<TIMER> CountDown for Timer<TIMER>
-where
-    TIMER: Deref<Target = CcuRegisterBlock> + CcuSliceGlobal,
-{
-    type Time = Hertz;
+    /// Stops the timer and releases the timer slice
+    ///
+    /// CCU40 is gated again if all slices are idle
+    pub fn release(self, scu: &mut Scu) -> TIMER {
+        // Stop the timer
+        self.tim.tcclr.write(|w| w.trbc().set_bit());
+        self.tim.idle();
+        // NOTE(unsafe) atomic read with no side effects
+        let gstat = unsafe { (*CCU40::ptr()).gstat.read() };
+        if gstat.s0i().bit_is_set()
+            && gstat.s1i().bit_is_set()
+            && gstat.s2i().bit_is_set()
+            && gstat.s3i().bit_is_set()
+        {
+            scu.disable_clock::<CCU40>();
+        }
+        self.tim
+    }
 
-    /// Start the timer with a `timeout`
-    fn start<T>(&mut self, timeout: T)
+    /// Uses updated clocks, e.g. after trimming
+    ///
+    /// Takes effect on the next `start`
+    pub fn update_clocks(&mut self, scu: &Scu) {
+        self.clocks = scu.clocks;
+    }
+
+    /// Tries to start the timer with a `timeout`
+    ///
+    /// Fails with the closest achievable timeout if `timeout` can't be reached
+    pub fn try_start<T>(&mut self, timeout: T) -> Result<(), TimerError>
     where
         T: Into<Hertz>,
     {
@@ -177,39 +304,113 @@ where
         // So we need to adjust PR & (prescaling)
         // Timer period is PR + 1, so we have to substract 1
         // Use a normal prescaler (psc.psiv) 2^n
-        let timeout = timeout.into();
-        let ticks = self.clocks.sysclk().0 / timeout.0.max(1);
+        let pclk = self.clocks.pclk().0;
+        let ticks = pclk / timeout.into().0.max(1);
+        if ticks == 0 {
+            let closest = Hertz(pclk);
+            return Err(TimerError::InvalidTimeout { closest });
+        }
         let divider = ((ticks >> 16) + 1).next_power_of_two();
+        if divider > 32768 {
+            // Round up, so the closest timeout can be reached
+            let closest = Hertz(pclk.div_ceil(32768 << 16));
+            return Err(TimerError::InvalidTimeout { closest });
+        }
         let pr = (ticks / divider) - 1;
         unsafe { self.tim.prs.write(|w| w.prs().bits(pr as u16)) };
         // Set the prescaler
-        // TODO Maybe modify next_power_of_two implementation to make this simpler
         unsafe {
-            self.tim.psc.write(|w| match divider {
-                1 => w.psiv().bits(0),
-                2 => w.psiv().bits(1),
-                4 => w.psiv().bits(2),
-                8 => w.psiv().bits(3),
-                16 => w.psiv().bits(4),
-                32 => w.psiv().bits(5),
-                64 => w.psiv().bits(6),
-                128 => w.psiv().bits(7),
-                256 => w.psiv().bits(8),
-                512 => w.psiv().bits(9),
-                1024 => w.psiv().bits(10),
-                2048 => w.psiv().bits(11),
-                4096 => w.psiv().bits(12),
-                8192 => w.psiv().bits(13),
-                16384 => w.psiv().bits(14),
-                32768 => w.psiv().bits(15),
-                _ => panic!("Timer prescaler value too large"),
-            })
+            self.tim
+                .psc
+                .write(|w| w.psiv().bits(divider.trailing_zeros() as u8))
         };
         self.tim.transfer_shadow();
         // Reset the timer count
         self.tim.tcclr.write(|w| w.tcc().set_bit());
         // Reset period match
         self.tim.swr.write(|w| w.rpm().set_bit());
+        Ok(())
+    }
+
+    /// Selects the input (0 = `CCU40.INyA` to 15 = `CCU40.INyP`) of an
+    /// external event and the edge which activates it
+    pub fn select_event_input(&mut self, event: ExternalEvent, input: u8, edge: Edge) {
+        debug_assert!(input < 16);
+        let edge = match edge {
+            Edge::Rising => 1,
+            Edge::Falling => 2,
+            Edge::Both => 3,
+        };
+        self.tim.ins.modify(|_, w| match event {
+            ExternalEvent::Event0 => w.ev0is().bits(input).ev0em().bits(edge),
+            ExternalEvent::Event1 => w.ev1is().bits(input).ev1em().bits(edge),
+            ExternalEvent::Event2 => w.ev2is().bits(input).ev2em().bits(edge),
+        });
+    }
+
+    /// Selects the gated trigger output `ERU0.GOUTx` of an output gating unit
+    /// as input of an external event
+    ///
+    /// The trigger pulses of the output gating unit are passed on according to
+    /// `Ogu::set_gating`
+    pub fn select_eru_event<CH: Channel>(
+        &mut self,
+        event: ExternalEvent,
+        _ogu: &Ogu<CH>,
+        edge: Edge,
+    ) {
+        self.select_event_input(event, TIMER::ERU_GOUT_INPUTS[CH::INDEX], edge);
+    }
+
+    /// Triggers a slice function by an external event, or only internally if
+    /// `event` is `None`
+    pub fn set_external_function(
+        &mut self,
+        function: ExternalFunction,
+        event: Option<ExternalEvent>,
+    ) {
+        let event = match event {
+            None => 0,
+            Some(ExternalEvent::Event0) => 1,
+            Some(ExternalEvent::Event1) => 2,
+            Some(ExternalEvent::Event2) => 3,
+        };
+        self.tim.cmc.modify(|_, w| match function {
+            ExternalFunction::Start => w.strts().bits(event),
+            ExternalFunction::Stop => w.ends().bits(event),
+            ExternalFunction::Capture0 => w.cap0s().bits(event),
+            ExternalFunction::Capture1 => w.cap1s().bits(event),
+        });
+    }
+
+    /// Returns the value captured by `ExternalFunction::Capture0`, if a new one
+    /// is available
+    pub fn captured(&self) -> Option<u16> {
+        let c1v = self.tim.c1v.read();
+        if c1v.ffl().bit_is_set() {
+            Some(c1v.captv().bits())
+        } else {
+            None
+        }
+    }
+}
+
+impl<TIMER> CountDown for Timer<TIMER>
+where
+    TIMER: Deref<Target = CcuRegisterBlock> + CcuSliceGlobal,
+{
+    type Time = Hertz;
+
+    /// Start the timer with a `timeout`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timeout can't be reached, see `try_start`
+    fn start<T>(&mut self, timeout: T)
+    where
+        T: Into<Hertz>,
+    {
+        self.try_start(timeout).expect("Timeout can't be reached");
     }
 
     /// Return `Ok` if the timer has wrapped
@@ -4,6 +4,9 @@ use core::ops::Deref;
 use xmc1100;
 
 pub trait Dout0Pin<USIC> {}
+pub trait Dout1Pin<USIC> {}
+pub trait Dout2Pin<USIC> {}
+pub trait Dout3Pin<USIC> {}
 
 pub trait Dx0Pin<USIC> {
     fn number() -> u8;
@@ -49,20 +52,76 @@ where
     }
 }
 
-pub(crate) fn set_baudrate<USIC>(
+/// Data lines of a USIC channel, which are controlled by its hardware port
+/// control (`CCR.HPCEN`), e.g. for dual and quad SPI
+///
+/// The USIC switches the direction of the pins, so they have to be configured
+/// with `into_hw_controlled_hw0`/`into_hw_controlled_hw1`. Enable the hardware
+/// port control after the protocol has been configured, which resets `CCR`.
+pub struct HwPortControl<PINS, USIC> {
+    pins: PINS,
+    phantom: PhantomData<USIC>,
+}
+
+/// Enables the hardware port control for `DOUT0` and `DOUT1`
+pub fn hw_port_control_dual<USIC, DOUT0, DOUT1>(
+    pins: (DOUT0, DOUT1),
     usic: &mut USIC,
-    scu: &mut Scu,
-    bps: Bps,
-    oversampling: u8,
-) -> Result<(), ()>
+) -> HwPortControl<(DOUT0, DOUT1), USIC>
+where
+    USIC: Deref<Target = UsicRegisterBlock>,
+    DOUT0: Dout0Pin<USIC>,
+    DOUT1: Dout1Pin<USIC>,
+{
+    usic.ccr.modify(|_, w| w.hpcen().value3());
+    HwPortControl {
+        pins,
+        phantom: PhantomData,
+    }
+}
+
+/// Enables the hardware port control for `DOUT0` to `DOUT3`
+pub fn hw_port_control_quad<USIC, DOUT0, DOUT1, DOUT2, DOUT3>(
+    pins: (DOUT0, DOUT1, DOUT2, DOUT3),
+    usic: &mut USIC,
+) -> HwPortControl<(DOUT0, DOUT1, DOUT2, DOUT3), USIC>
 where
     USIC: Deref<Target = UsicRegisterBlock>,
+    DOUT0: Dout0Pin<USIC>,
+    DOUT1: Dout1Pin<USIC>,
+    DOUT2: Dout2Pin<USIC>,
+    DOUT3: Dout3Pin<USIC>,
 {
+    usic.ccr.modify(|_, w| w.hpcen().value4());
+    HwPortControl {
+        pins,
+        phantom: PhantomData,
+    }
+}
+
+impl<PINS, USIC> HwPortControl<PINS, USIC>
+where
+    USIC: Deref<Target = UsicRegisterBlock>,
+{
+    /// Disables the hardware port control and releases the pins
+    pub fn release(self, usic: &mut USIC) -> PINS {
+        usic.ccr.modify(|_, w| w.hpcen().value1());
+        self.pins
+    }
+}
+
+pub(crate) fn set_baudrate(
+    usic: &UsicRegisterBlock,
+    scu: &Scu,
+    bps: Bps,
+    oversampling: u8,
+) -> Result<(), ()> {
     // Pretty much the code from XMCLib
-    let peripheral_clock = scu.clocks.sysclk().0 / 100;
+    // USIC0 is clocked from MCLK, only CCU4 runs from PCLK
+    let peripheral_clock = scu.clocks.mclk().0 / 100;
     let mut clock_divider_min = 1;
-    let mut pdiv_int_min = 1;
-    let mut pdiv_frac_min = 0x3FF;
+    let mut pdiv_int_min = 0;
+    let mut pdiv_frac_min = 0x400;
     if bps.0 < 100 {
         return Err(());
     }
@@ -71,12 +130,17 @@ where
         let pdiv = (peripheral_clock * clock_divider) / (rate * oversampling as u32);
         let pdiv_int = pdiv >> 10;
         let pdiv_frac = pdiv & 0x3ff;
-        if (pdiv_int < 1024) && (pdiv_frac < pdiv_frac_min) {
+        // A divider below 1 means the rate is too high for this step
+        if (1..1024).contains(&pdiv_int) && (pdiv_frac < pdiv_frac_min) {
             pdiv_frac_min = pdiv_frac;
             pdiv_int_min = pdiv_int;
             clock_divider_min = clock_divider;
         }
     }
+    if pdiv_int_min == 0 {
+        // No usable divider, the rate can't be reached from MCLK
+        return Err(());
+    }
     unsafe {
         usic.fdr
             .write(|w| w.dm().value3().step().bits(clock_divider_min as u16))